@@ -1,3 +1,4 @@
+use crate::entities::area::BoundingBox;
 use std::str::FromStr;
 
 pub enum Areas {
@@ -7,6 +8,17 @@ pub enum Areas {
     Dummy,
 }
 
+impl Areas {
+    pub fn get_bounding_box(&self) -> BoundingBox {
+        match self {
+            Areas::London => BoundingBox::new(51.2424, -0.5637, 51.7334, 0.3114), // very dense
+            Areas::Belgium => BoundingBox::new(49.421, 2.4153, 51.532, 6.5626), // medium dense
+            Areas::Pyrenees => BoundingBox::new(41.8872, -1.9133, 43.4263, 3.3382), // sparse
+            Areas::Dummy => BoundingBox::new(51.15, 4.40, 51.25, 4.5), // tiny piece of belgium
+        }
+    }
+}
+
 impl FromStr for Areas {
     type Err = &'static str;
 
@@ -20,3 +32,28 @@ impl FromStr for Areas {
         }
     }
 }
+
+impl FromStr for BoundingBox {
+    type Err = &'static str;
+
+    // min_lat,min_lon,max_lat,max_lon
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values: Vec<f64> = s
+            .split(',')
+            .map(|v| v.trim().parse::<f64>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| "bounding box values should be numbers")?;
+
+        if let [min_lat, min_lon, max_lat, max_lon] = values[..] {
+            if min_lat > max_lat || min_lon > max_lon {
+                return Err("bounding box minimum exceeds its maximum");
+            }
+            if min_lat < -85.0511 || max_lat > 85.0511 || min_lon < -180. || max_lon > 180. {
+                return Err("bounding box lies outside of the web mercator projection");
+            }
+            Ok(BoundingBox::new(min_lat, min_lon, max_lat, max_lon))
+        } else {
+            Err("expected min_lat,min_lon,max_lat,max_lon")
+        }
+    }
+}
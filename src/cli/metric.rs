@@ -0,0 +1,28 @@
+use crate::entities::weighted_tile::Metric;
+use std::str::FromStr;
+
+impl FromStr for Metric {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cost" => Ok(Metric::Cost),
+            "duration" => Ok(Metric::Duration),
+            "distance" => Ok(Metric::Distance),
+            _ => Err("expected cost, duration or distance"),
+        }
+    }
+}
+
+// every metric becomes a column of weights, so each may only be given once
+pub fn parse_metrics<'a>(values: impl Iterator<Item = &'a str>) -> Result<Vec<Metric>, String> {
+    let mut result = Vec::new();
+    for value in values {
+        let metric: Metric = value.parse().map_err(|e| format!("invalid metric `{}`: {}", value, e))?;
+        if result.contains(&metric) {
+            return Err(format!("metric `{}` is given more than once", value));
+        }
+        result.push(metric);
+    }
+    Ok(result)
+}
@@ -1,3 +1,3 @@
 pub mod area;
+pub mod metric;
 pub mod task;
-pub mod profile;
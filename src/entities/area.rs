@@ -0,0 +1,169 @@
+use crate::entities::tile_coord::TileCoordinate;
+use crate::util::get_tile_edges;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min_lat: f64,
+    pub min_lon: f64,
+    pub max_lat: f64,
+    pub max_lon: f64,
+}
+
+impl BoundingBox {
+    pub fn new(min_lat: f64, min_lon: f64, max_lat: f64, max_lon: f64) -> BoundingBox {
+        BoundingBox {
+            min_lat,
+            min_lon,
+            max_lat,
+            max_lon,
+        }
+    }
+
+    pub fn get_lats(&self) -> [f64; 2] {
+        [self.min_lat, self.max_lat]
+    }
+
+    pub fn get_lons(&self) -> [f64; 2] {
+        [self.min_lon, self.max_lon]
+    }
+}
+
+// rings are stored as GeoJSON does: [lon, lat] pairs
+#[derive(Debug, Clone)]
+pub struct Polygon {
+    exterior: Vec<[f64; 2]>,
+    holes: Vec<Vec<[f64; 2]>>,
+}
+
+impl Polygon {
+    pub fn new(exterior: Vec<[f64; 2]>, holes: Vec<Vec<[f64; 2]>>) -> Polygon {
+        Polygon { exterior, holes }
+    }
+
+    pub fn get_bounding_box(&self) -> BoundingBox {
+        let mut result = BoundingBox::new(
+            std::f64::MAX,
+            std::f64::MAX,
+            std::f64::MIN,
+            std::f64::MIN,
+        );
+        for [lon, lat] in self.exterior.iter() {
+            result.min_lat = result.min_lat.min(*lat);
+            result.min_lon = result.min_lon.min(*lon);
+            result.max_lat = result.max_lat.max(*lat);
+            result.max_lon = result.max_lon.max(*lon);
+        }
+        result
+    }
+
+    pub fn contains(&self, lat: f64, lon: f64) -> bool {
+        ring_contains(&self.exterior, lat, lon)
+            && !self.holes.iter().any(|hole| ring_contains(hole, lat, lon))
+    }
+
+    pub fn intersects_tile(&self, coord: &TileCoordinate) -> bool {
+        let bounds = get_tile_edges(coord);
+
+        // if no ring crosses the tile, the tile is either entirely inside or entirely outside
+        let crosses = std::iter::once(&self.exterior)
+            .chain(self.holes.iter())
+            .any(|ring| ring_intersects_rectangle(ring, bounds));
+        if crosses {
+            return true;
+        }
+
+        let [e, n, w, s] = bounds;
+        self.contains((n + s) / 2., (e + w) / 2.)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Area {
+    BoundingBox(BoundingBox),
+    Polygons(Vec<Polygon>),
+}
+
+impl Area {
+    pub fn get_bounding_box(&self) -> BoundingBox {
+        match self {
+            Area::BoundingBox(bbox) => *bbox,
+            Area::Polygons(polygons) => {
+                let mut result = BoundingBox::new(
+                    std::f64::MAX,
+                    std::f64::MAX,
+                    std::f64::MIN,
+                    std::f64::MIN,
+                );
+                for polygon in polygons {
+                    let bbox = polygon.get_bounding_box();
+                    result.min_lat = result.min_lat.min(bbox.min_lat);
+                    result.min_lon = result.min_lon.min(bbox.min_lon);
+                    result.max_lat = result.max_lat.max(bbox.max_lat);
+                    result.max_lon = result.max_lon.max(bbox.max_lon);
+                }
+                result
+            }
+        }
+    }
+
+    pub fn intersects_tile(&self, coord: &TileCoordinate) -> bool {
+        match self {
+            Area::BoundingBox(_) => true,
+            Area::Polygons(polygons) => polygons.iter().any(|p| p.intersects_tile(coord)),
+        }
+    }
+}
+
+// even-odd rule
+fn ring_contains(ring: &[[f64; 2]], lat: f64, lon: f64) -> bool {
+    let mut inside = false;
+    if ring.is_empty() {
+        return inside;
+    }
+
+    let mut j = ring.len() - 1;
+    for i in 0..ring.len() {
+        let [xi, yi] = ring[i];
+        let [xj, yj] = ring[j];
+        if (yi > lat) != (yj > lat) && lon < (xj - xi) * (lat - yi) / (yj - yi) + xi {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+fn ring_intersects_rectangle(ring: &[[f64; 2]], bounds: [f64; 4]) -> bool {
+    ring.windows(2)
+        .any(|pair| segment_intersects_rectangle(pair[0], pair[1], bounds))
+}
+
+// Liang-Barsky clipping, only checks whether anything remains of the segment
+fn segment_intersects_rectangle(from: [f64; 2], to: [f64; 2], bounds: [f64; 4]) -> bool {
+    let [e, n, w, s] = bounds;
+    let [x0, y0] = from;
+    let dx = to[0] - x0;
+    let dy = to[1] - y0;
+
+    let mut t0 = 0f64;
+    let mut t1 = 1f64;
+    let checks = [(-dx, x0 - w), (dx, e - x0), (-dy, y0 - s), (dy, n - y0)];
+    for (p, q) in checks.iter() {
+        if *p == 0. {
+            if *q < 0. {
+                return false;
+            }
+        } else {
+            let r = q / p;
+            if *p < 0. {
+                t0 = t0.max(r);
+            } else {
+                t1 = t1.min(r);
+            }
+            if t0 > t1 {
+                return false;
+            }
+        }
+    }
+    true
+}
@@ -0,0 +1,205 @@
+use crate::entities::node::Node;
+use crate::entities::profile::{get_travel_time, Conclusion, Direction, Profile, Rule, RuleKind};
+use crate::entities::way::Way;
+use crate::entities::weighted_tile::Metric;
+use crate::util::haversine::get_distance;
+use std::collections::{BTreeMap, HashMap};
+
+// everything the graph builders need to know about a way
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WayProfile {
+    pub access: bool,
+    pub direction: Direction,
+    pub speed: f64,
+    pub multiplier: f64,
+}
+
+// everything the graph builders need to know about a node
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NodeProfile {
+    pub obstacle: bool,
+    pub obstacle_time: f64,
+}
+
+// Index of a single (sorted) rule list.
+// Plain equality rules are looked up by tag, all other rules are evaluated in order.
+struct CompiledRules {
+    by_tag: HashMap<String, HashMap<String, usize>>,
+    others: Vec<usize>,
+}
+
+impl CompiledRules {
+    fn new(rules: &[Rule]) -> CompiledRules {
+        let mut by_tag: HashMap<String, HashMap<String, usize>> = HashMap::new();
+        let mut others = Vec::new();
+
+        for (index, rule) in rules.iter().enumerate() {
+            match rule.get_condition().and_then(|c| c.get_simple_tag()) {
+                Some((key, value)) => {
+                    // keep the first occurrence, that one has the highest priority
+                    by_tag
+                        .entry(key.to_owned())
+                        .or_default()
+                        .entry(value.to_owned())
+                        .or_insert(index);
+                }
+                None => others.push(index),
+            }
+        }
+
+        CompiledRules { by_tag, others }
+    }
+
+    fn find<'a>(&self, rules: &'a [Rule], tags: &BTreeMap<String, String>) -> Option<&'a Conclusion> {
+        let mut best = tags
+            .iter()
+            .filter_map(|(key, value)| self.by_tag.get(key).and_then(|m| m.get(value)))
+            .min()
+            .copied();
+
+        for &index in self.others.iter() {
+            if best.map_or(false, |b| index > b) {
+                break;
+            }
+            if rules[index].matches(tags) {
+                best = Some(index);
+                break;
+            }
+        }
+
+        best.map(|index| rules[index].get_conclusion())
+    }
+}
+
+// Produces the same results as the `Profile` it was created from, but faster.
+pub struct CompiledProfile {
+    profile: Profile,
+    access: CompiledRules,
+    oneway: CompiledRules,
+    speed: CompiledRules,
+    priority: CompiledRules,
+    obstacle: CompiledRules,
+    obstacle_time: CompiledRules,
+}
+
+impl CompiledProfile {
+    pub fn new(profile: Profile) -> CompiledProfile {
+        CompiledProfile {
+            access: CompiledRules::new(profile.get_rules(RuleKind::Access)),
+            oneway: CompiledRules::new(profile.get_rules(RuleKind::Oneway)),
+            speed: CompiledRules::new(profile.get_rules(RuleKind::Speed)),
+            priority: CompiledRules::new(profile.get_rules(RuleKind::Priority)),
+            obstacle: CompiledRules::new(profile.get_rules(RuleKind::Obstacle)),
+            obstacle_time: CompiledRules::new(profile.get_rules(RuleKind::ObstacleTime)),
+            profile,
+        }
+    }
+
+    pub fn get_profile(&self) -> &Profile {
+        &self.profile
+    }
+
+    fn find(&self, kind: RuleKind, tags: &BTreeMap<String, String>) -> Option<&Conclusion> {
+        let compiled = match kind {
+            RuleKind::Access => &self.access,
+            RuleKind::Oneway => &self.oneway,
+            RuleKind::Speed => &self.speed,
+            RuleKind::Priority => &self.priority,
+            RuleKind::Obstacle => &self.obstacle,
+            RuleKind::ObstacleTime => &self.obstacle_time,
+        };
+        compiled.find(self.profile.get_rules(kind), tags)
+    }
+
+    pub fn evaluate_way(&self, way: &Way) -> WayProfile {
+        let tags = way.get_tags();
+        let access = self
+            .find(RuleKind::Access, tags)
+            .map(Conclusion::get_access)
+            .unwrap_or(true);
+        let direction = self
+            .find(RuleKind::Oneway, tags)
+            .map(Conclusion::get_direction)
+            .unwrap_or(Direction::Both);
+        let speed = self.profile.limit_speed(
+            self.find(RuleKind::Speed, tags).map(Conclusion::get_speed),
+            way,
+        );
+        let multiplier = self
+            .find(RuleKind::Priority, tags)
+            .map(Conclusion::get_multiplier)
+            .unwrap_or(1.);
+
+        WayProfile {
+            access,
+            direction,
+            speed,
+            multiplier,
+        }
+    }
+
+    pub fn evaluate_node(&self, node: &Node) -> NodeProfile {
+        let tags = node.get_tags();
+        let obstacle = self
+            .find(RuleKind::Obstacle, tags)
+            .map(Conclusion::is_obstacle)
+            .unwrap_or(false);
+        let obstacle_time = self
+            .find(RuleKind::ObstacleTime, tags)
+            .map(Conclusion::get_obstacle_time)
+            .unwrap_or(0.);
+
+        NodeProfile {
+            obstacle,
+            obstacle_time,
+        }
+    }
+
+    pub fn has_access(&self, way: &Way) -> bool {
+        self.find(RuleKind::Access, way.get_tags())
+            .map(Conclusion::get_access)
+            .unwrap_or(true)
+    }
+
+    // identical to `Profile::get_duration`, given the evaluated way
+    pub fn get_duration(&self, from: &Node, to: &Node, way: &WayProfile) -> f64 {
+        get_travel_time(get_distance(from, to), way.speed)
+    }
+
+    // identical to `Profile::get_cost`, given the evaluated way and destination node
+    pub fn get_cost(&self, from: &Node, to: &Node, way: &WayProfile, to_profile: &NodeProfile) -> f64 {
+        let base = way.multiplier * (self.get_duration(from, to, way) + to_profile.obstacle_time);
+        base.max(1.)
+    }
+
+    pub fn get_weight(&self, metric: Metric, from: &Node, to: &Node, way: &WayProfile, to_profile: &NodeProfile) -> f64 {
+        match metric {
+            Metric::Cost => self.get_cost(from, to, way, to_profile),
+            Metric::Duration => self.get_duration(from, to, way) + to_profile.obstacle_time,
+            Metric::Distance => get_distance(from, to) * 1000.,
+        }
+    }
+}
+
+// evaluates every node at most once
+pub struct NodeProfileCache<'a> {
+    profile: &'a CompiledProfile,
+    cache: HashMap<&'a str, NodeProfile>,
+}
+
+impl<'a> NodeProfileCache<'a> {
+    pub fn new(profile: &'a CompiledProfile) -> NodeProfileCache<'a> {
+        NodeProfileCache {
+            profile,
+            cache: HashMap::new(),
+        }
+    }
+
+    pub fn get(&mut self, node: &'a Node) -> NodeProfile {
+        let profile = self.profile;
+        *self
+            .cache
+            .entry(node.get_id())
+            .or_insert_with(|| profile.evaluate_node(node))
+    }
+}
@@ -1,8 +1,13 @@
+pub mod area;
+pub mod compiled_profile;
 pub mod segment;
 pub mod graph;
 pub mod node;
 pub mod tile;
 pub mod tile_coord;
+pub mod turn_graph;
 pub mod way;
 pub mod profile;
+pub mod property;
+pub mod relation;
 pub mod weighted_tile;
\ No newline at end of file
@@ -1,12 +1,14 @@
 use std::collections::BTreeMap;
+use crate::entities::property::Property;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Node {
     id: String,
     lat: f64,
     long: f64,
     tags: BTreeMap<String, String>,
     undefined_tags: Vec<String>,
+    properties: BTreeMap<String, Property>, // the values that aren't plain strings
 }
 
 impl Node {
@@ -17,7 +19,7 @@ impl Node {
         tags: BTreeMap<String, String>,
         undefined_tags: Vec<String>,
     ) -> Node {
-        Node {id, lat, long, tags, undefined_tags}
+        Node {id, lat, long, tags, undefined_tags, properties: BTreeMap::new()}
     }
 
     pub fn get_id(&self) -> &str {
@@ -39,4 +41,12 @@ impl Node {
     pub fn get_undefined_tags(&self) -> &[String] {
         &self.undefined_tags
     }
+
+    pub fn get_properties(&self) -> &BTreeMap<String, Property> {
+        &self.properties
+    }
+
+    pub fn set_properties(&mut self, properties: BTreeMap<String, Property>) {
+        self.properties = properties;
+    }
 }
\ No newline at end of file
@@ -1,31 +1,222 @@
 #![allow(non_snake_case)]
+#[cfg(test)]
 use crate::util::haversine::get_distance;
-use serde::{Deserialize, Serialize};
+use crate::util::units::parse_numeric_tag;
+use serde::{Deserialize, Deserializer, Serialize};
 use crate::entities::way::Way;
+#[cfg(test)]
 use crate::entities::node::Node;
-use std::collections::HashSet;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
 
+// every rule list is sorted by `hasOrder` when the profile gets loaded,
+// so the evaluation functions below can simply return the first match
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Profile {
     hasMaxSpeed: Option<f64>,
+    hasTransportMode: Option<String>,
+    #[serde(deserialize_with = "deserialize_ordered_rules")]
     hasAccessRules: Vec<Rule>,
+    #[serde(deserialize_with = "deserialize_ordered_rules")]
     hasOnewayRules: Vec<Rule>,
+    #[serde(deserialize_with = "deserialize_ordered_rules")]
     hasSpeedRules: Vec<Rule>,
+    #[serde(deserialize_with = "deserialize_ordered_rules")]
     hasPriorityRules: Vec<Rule>,
+    #[serde(deserialize_with = "deserialize_ordered_rules")]
     hasObstacleRules: Vec<Rule>,
+    #[serde(deserialize_with = "deserialize_ordered_rules")]
     hasObstacleTimeRules: Vec<Rule>,
+    #[serde(skip)]
+    source: ProfileSource,
+}
+
+// where a profile was loaded from, so derived data can refer back to it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProfileSource {
+    pub name: String,
+    pub hash: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Rule {
     r#match: Option<Condition>,
     concludes: Conclusion,
+    hasOrder: Option<f64>,
+}
+
+impl Rule {
+    pub fn get_condition(&self) -> Option<&Condition> {
+        self.r#match.as_ref()
+    }
+
+    pub fn get_conclusion(&self) -> &Conclusion {
+        &self.concludes
+    }
+
+    pub fn matches(&self, tags: &BTreeMap<String, String>) -> bool {
+        self.r#match.as_ref().map_or(true, |c| c.matches(tags))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleKind {
+    Access,
+    Oneway,
+    Speed,
+    Priority,
+    Obstacle,
+    ObstacleTime,
+}
+
+impl RuleKind {
+    pub fn all() -> [RuleKind; 6] {
+        [
+            RuleKind::Access,
+            RuleKind::Oneway,
+            RuleKind::Speed,
+            RuleKind::Priority,
+            RuleKind::Obstacle,
+            RuleKind::ObstacleTime,
+        ]
+    }
+}
+
+// Rules are evaluated by ascending `hasOrder`.
+// Rules with an equal order keep their position in the file,
+// and rules without an order come after all ordered rules.
+fn compare_rules(a: &Rule, b: &Rule) -> Ordering {
+    match (a.hasOrder, b.hasOrder) {
+        (Some(x), Some(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
 }
 
+fn deserialize_ordered_rules<'de, D>(deserializer: D) -> Result<Vec<Rule>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut rules = Vec::<Rule>::deserialize(deserializer)?;
+    rules.sort_by(compare_rules); // stable
+    Ok(rules)
+}
+
+// A condition holds if all of its parts hold:
+// - `hasPredicate` and `hasObject`: the tag has exactly that value
+// - only `hasPredicate`: the tag is present
+// - only `hasObject`: any tag has that value
+// - `lessThan`, `greaterThan`, ...: the numeric value of the `hasPredicate` tag compares as such,
+//   tags without a (parsable) numeric value never match
+// - `and`, `or` and `not` combine nested conditions
+// A condition without any parts always holds.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Condition {
     hasPredicate: Option<String>,
     hasObject: Option<String>,
+    lessThan: Option<f64>,
+    lessThanOrEqual: Option<f64>,
+    greaterThan: Option<f64>,
+    greaterThanOrEqual: Option<f64>,
+    and: Option<Vec<Condition>>,
+    or: Option<Vec<Condition>>,
+    not: Option<Box<Condition>>,
+}
+
+impl Condition {
+    pub fn matches(&self, tags: &BTreeMap<String, String>) -> bool {
+        let tag_match = match (&self.hasPredicate, &self.hasObject) {
+            (Some(key), Some(value)) => tags.get(key) == Some(value),
+            (Some(key), None) => tags.contains_key(key),
+            (None, Some(value)) => tags.values().any(|v| v == value),
+            (None, None) => true,
+        };
+
+        tag_match
+            && self.numeric_match(tags)
+            && self.and.as_ref().map_or(true, |c| c.iter().all(|c| c.matches(tags)))
+            && self.or.as_ref().map_or(true, |c| c.iter().any(|c| c.matches(tags)))
+            && self.not.as_ref().map_or(true, |c| !c.matches(tags))
+    }
+
+    // the (predicate, object) pair if this is nothing more than a plain equality check
+    pub fn get_simple_tag(&self) -> Option<(&str, &str)> {
+        match (&self.hasPredicate, &self.hasObject) {
+            (Some(key), Some(value)) if !self.has_comparison() && self.get_nested().next().is_none() => {
+                Some((key, value))
+            }
+            _ => None,
+        }
+    }
+
+    fn has_comparison(&self) -> bool {
+        self.lessThan.is_some()
+            || self.lessThanOrEqual.is_some()
+            || self.greaterThan.is_some()
+            || self.greaterThanOrEqual.is_some()
+    }
+
+    fn numeric_match(&self, tags: &BTreeMap<String, String>) -> bool {
+        if !self.has_comparison() {
+            return true;
+        }
+
+        let value = self
+            .hasPredicate
+            .as_ref()
+            .and_then(|key| tags.get(key).and_then(|value| parse_numeric_tag(key, value)));
+
+        match value {
+            Some(value) => {
+                self.lessThan.map_or(true, |bound| value < bound)
+                    && self.lessThanOrEqual.map_or(true, |bound| value <= bound)
+                    && self.greaterThan.map_or(true, |bound| value > bound)
+                    && self.greaterThanOrEqual.map_or(true, |bound| value >= bound)
+            }
+            None => false,
+        }
+    }
+
+    fn extract_concepts(&self, result: &mut HashSet<String>) {
+        if let Some(uri) = &self.hasPredicate {
+            result.insert(uri.to_string());
+        }
+        if let Some(uri) = &self.hasObject {
+            result.insert(uri.to_string());
+        }
+
+        for condition in self.get_nested() {
+            condition.extract_concepts(result);
+        }
+    }
+
+    // predicates whose values matter regardless of what they are
+    fn extract_open_predicates(&self, result: &mut HashSet<String>) {
+        if let (Some(uri), None) = (&self.hasPredicate, &self.hasObject) {
+            result.insert(uri.to_string());
+        }
+
+        for condition in self.get_nested() {
+            condition.extract_open_predicates(result);
+        }
+    }
+
+    // objects that are matched without a predicate, they can be the value of any tag
+    fn extract_open_objects(&self, result: &mut HashSet<String>) {
+        if let (None, Some(uri)) = (&self.hasPredicate, &self.hasObject) {
+            result.insert(uri.to_string());
+        }
+
+        for condition in self.get_nested() {
+            condition.extract_open_objects(result);
+        }
+    }
+
+    fn get_nested(&self) -> impl Iterator<Item = &Condition> {
+        let nested = self.and.iter().chain(self.or.iter()).flatten();
+        nested.chain(self.not.iter().map(|c| c.as_ref()))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -39,71 +230,146 @@ pub struct Conclusion {
     hasPriority: Option<f64>,
 }
 
+impl Conclusion {
+    pub fn get_access(&self) -> bool {
+        self.hasAccess.unwrap()
+    }
+
+    pub fn get_direction(&self) -> Direction {
+        Direction::from_conclusion(self)
+    }
+
+    pub fn get_speed(&self) -> f64 {
+        self.hasSpeed.unwrap()
+    }
+
+    pub fn get_multiplier(&self) -> f64 {
+        1. / self.hasPriority.unwrap()
+    }
+
+    pub fn is_obstacle(&self) -> bool {
+        self.isObstacle.unwrap()
+    }
+
+    pub fn get_obstacle_time(&self) -> f64 {
+        self.hasObstacleTime.unwrap() * 1000. // ms
+    }
+}
+
+fn get_parent_mode(mode: &str) -> Option<&'static str> {
+    match mode {
+        "motorcar" | "motorcycle" | "moped" | "mofa" | "hgv" | "bus" | "goods" | "psv" => Some("motor_vehicle"),
+        "motor_vehicle" | "bicycle" | "carriage" => Some("vehicle"),
+        _ => None,
+    }
+}
+
+pub fn get_travel_time(distance: f64, speed: f64) -> f64 {
+    let time = distance / speed; // h
+    time * 60. *60. * 1000. // ms
+}
+
+// the direction(s) in which a way can be traversed, relative to the order of its nodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Forward,
+    Backward,
+    Both,
+}
+
+impl Direction {
+    fn from_conclusion(conclusion: &Conclusion) -> Direction {
+        match (conclusion.isOneway, conclusion.isReversed) {
+            (Some(true), Some(true)) => Direction::Backward,
+            (Some(true), _) => Direction::Forward,
+            _ => Direction::Both,
+        }
+    }
+
+    pub fn allows_forward(self) -> bool {
+        self != Direction::Backward
+    }
+
+    pub fn allows_backward(self) -> bool {
+        self != Direction::Forward
+    }
+}
+
 impl Profile {
     pub fn get_used_concepts(&self) -> HashSet<String> {
         let mut result = HashSet::new();
 
         let mut extract = |rules: &[Rule]| {
             for rule in rules.iter() {
-                if let Some(Condition {hasPredicate, hasObject}) = &rule.r#match {
-                    if let Some(uri) = hasPredicate {
-                        result.insert(uri.to_string());
-                    }
-                    if let Some(uri) = hasObject {
-                        result.insert(uri.to_string());
-                    }
+                if let Some(condition) = &rule.r#match {
+                    condition.extract_concepts(&mut result);
                 }
             }
         };
 
-        extract(&self.hasAccessRules);
-        extract(&self.hasOnewayRules);
-        extract(&self.hasSpeedRules);
-        extract(&self.hasPriorityRules);
-        extract(&self.hasObstacleRules);
-        extract(&self.hasObstacleTimeRules);
+        for kind in RuleKind::all().iter() {
+            extract(self.get_rules(*kind));
+        }
         
         result
     }
 
-    pub fn is_one_way(&self, way: &Way) -> bool {
-        for rule in &self.hasOnewayRules {
-            let conclusion = rule.concludes.isOneway.unwrap();
-            if let Some(ref condition) = rule.r#match {
-                if let Condition {
-                    hasObject: Some(ref value),
-                    hasPredicate: Some(ref key)
-                } = condition {
-                    if way.get_tags().contains_key(key) && &way.get_tags()[key] == value {
-                        return conclusion
-                    }
+    // predicates that are matched without an object, e.g. presence checks or numeric comparisons
+    // all values of these tags have to be retained
+    pub fn get_open_predicates(&self) -> HashSet<String> {
+        let mut result = HashSet::new();
+
+        let mut extract = |rules: &[Rule]| {
+            for rule in rules.iter() {
+                if let Some(condition) = &rule.r#match {
+                    condition.extract_open_predicates(&mut result);
                 }
-            } else {
-                return conclusion;
             }
+        };
+
+        for kind in RuleKind::all().iter() {
+            extract(self.get_rules(*kind));
         }
 
-        false
+        result
     }
 
-    pub fn has_access(&self, way: &Way) -> bool {
-        for rule in &self.hasAccessRules {
-            let conclusion = rule.concludes.hasAccess.unwrap();
-            if let Some(ref condition) = rule.r#match {
-                if let Condition {
-                    hasObject: Some(ref value),
-                    hasPredicate: Some(ref key)
-                } = condition {
-                    if way.get_tags().contains_key(key) && &way.get_tags()[key] == value {
-                        return conclusion
-                    }
+    // tags with one of these values have to be retained, whatever their predicate is
+    pub fn get_open_objects(&self) -> HashSet<String> {
+        let mut result = HashSet::new();
+
+        let mut extract = |rules: &[Rule]| {
+            for rule in rules.iter() {
+                if let Some(condition) = &rule.r#match {
+                    condition.extract_open_objects(&mut result);
                 }
-            } else {
-                return conclusion;
             }
+        };
+
+        for kind in RuleKind::all().iter() {
+            extract(self.get_rules(*kind));
         }
 
-        true
+        result
+    }
+
+    pub fn get_source(&self) -> &ProfileSource {
+        &self.source
+    }
+
+    pub fn set_source(&mut self, source: ProfileSource) {
+        self.source = source;
+    }
+
+    pub fn get_rules(&self, kind: RuleKind) -> &[Rule] {
+        match kind {
+            RuleKind::Access => &self.hasAccessRules,
+            RuleKind::Oneway => &self.hasOnewayRules,
+            RuleKind::Speed => &self.hasSpeedRules,
+            RuleKind::Priority => &self.hasPriorityRules,
+            RuleKind::Obstacle => &self.hasObstacleRules,
+            RuleKind::ObstacleTime => &self.hasObstacleTimeRules,
+        }
     }
 
     pub fn get_default_speed(&self) -> f64 {
@@ -114,56 +380,68 @@ impl Profile {
         self.hasMaxSpeed.unwrap_or(300.)
     }
 
-    pub fn get_speed(&self, way: &Way) -> f64 {
+    // the profile's mode followed by the more general ones, as in the OSM access hierarchy,
+    // profiles without a mode count as a generic vehicle
+    pub fn get_transport_modes(&self) -> Vec<&str> {
+        let mut modes = vec![self.hasTransportMode.as_deref().unwrap_or("vehicle")];
+        while let Some(parent) = get_parent_mode(modes[modes.len() - 1]) {
+            modes.push(parent);
+        }
+        modes
+    }
+
+    // combines the speed of the matching rule with the legal and the profile's speed limits
+    pub fn limit_speed(&self, speed: Option<f64>, way: &Way) -> f64 {
         let speed_limit = way
             .get_max_speed()
             .unwrap_or(std::f64::MAX)
             .min(self.get_max_speed());
 
-        for rule in &self.hasSpeedRules {
-            let conclusion = rule.concludes.hasSpeed.unwrap();
-            if let Some(ref condition) = rule.r#match {
-                if let Condition {
-                    hasObject: Some(ref value),
-                    hasPredicate: Some(ref key)
-                } = condition {
-                    if way.get_tags().contains_key(key) && &way.get_tags()[key] == value {
-                        return conclusion.min(speed_limit);
-                    }
-                }
-            } else {
-                return conclusion.min(speed_limit);
-            }
+        match speed {
+            Some(speed) => speed.min(speed_limit),
+            None => speed_limit.min(self.get_default_speed()),
         }
+    }
+}
+
+// The straightforward evaluator, which simply scans the rule lists.
+// The graph builders use `CompiledProfile` instead, this one serves as its reference in the tests.
+#[cfg(test)]
+impl Profile {
+    // rules are sorted, so the first match is the one with the highest priority
+    fn find_conclusion<'a>(rules: &'a [Rule], tags: &BTreeMap<String, String>) -> Option<&'a Conclusion> {
+        rules
+            .iter()
+            .find(|rule| rule.matches(tags))
+            .map(|rule| &rule.concludes)
+    }
+
+    pub fn get_direction(&self, way: &Way) -> Direction {
+        Profile::find_conclusion(&self.hasOnewayRules, way.get_tags())
+            .map(Conclusion::get_direction)
+            .unwrap_or(Direction::Both)
+    }
 
-        speed_limit.min(self.get_default_speed())
+    pub fn has_access(&self, way: &Way) -> bool {
+        Profile::find_conclusion(&self.hasAccessRules, way.get_tags())
+            .map(Conclusion::get_access)
+            .unwrap_or(true)
+    }
+
+    pub fn get_speed(&self, way: &Way) -> f64 {
+        let speed = Profile::find_conclusion(&self.hasSpeedRules, way.get_tags()).map(Conclusion::get_speed);
+        self.limit_speed(speed, way)
     }
 
     pub fn get_duration(&self, from: &Node, to: &Node, way: &Way) -> f64 {
         let distance = get_distance(from, to);
-        let speed = self.get_speed(way);
-        let time = distance / speed; // h
-        time * 60. *60. * 1000. // ms
+        get_travel_time(distance, self.get_speed(way))
     }
 
     pub fn get_multiplier(&self, way: &Way) -> f64 {
-        for rule in &self.hasPriorityRules {
-            let conclusion = rule.concludes.hasPriority.unwrap();
-            if let Some(ref condition) = rule.r#match {
-                if let Condition {
-                    hasObject: Some(ref value),
-                    hasPredicate: Some(ref key)
-                } = condition {
-                    if way.get_tags().contains_key(key) && &way.get_tags()[key] == value {
-                        return 1. / conclusion;
-                    }
-                }
-            } else {
-                return 1. / conclusion;
-            }
-        }
-
-        1.
+        Profile::find_conclusion(&self.hasPriorityRules, way.get_tags())
+            .map(Conclusion::get_multiplier)
+            .unwrap_or(1.)
     }
 
     pub fn get_cost(&self, from: &Node, to: &Node, way: &Way) -> f64 {
@@ -173,42 +451,14 @@ impl Profile {
     }
 
     pub fn is_obstacle(&self, node: &Node) -> bool {
-        for rule in &self.hasObstacleRules {
-            let conclusion = rule.concludes.isObstacle.unwrap();
-            if let Some(ref condition) = rule.r#match {
-                if let Condition {
-                    hasObject: Some(ref value),
-                    hasPredicate: Some(ref key)
-                } = condition {
-                    if node.get_tags().contains_key(key) && &node.get_tags()[key] == value {
-                        return conclusion
-                    }
-                }
-            } else {
-                return conclusion;
-            }
-        }
-
-        false
+        Profile::find_conclusion(&self.hasObstacleRules, node.get_tags())
+            .map(Conclusion::is_obstacle)
+            .unwrap_or(false)
     }
 
     pub fn get_obstacle_time(&self, node: &Node) -> f64 {
-        for rule in &self.hasObstacleTimeRules {
-            let conclusion = rule.concludes.hasObstacleTime.unwrap();
-            if let Some(ref condition) = rule.r#match {
-                if let Condition {
-                    hasObject: Some(ref value),
-                    hasPredicate: Some(ref key)
-                } = condition {
-                    if node.get_tags().contains_key(key) && &node.get_tags()[key] == value {
-                        return conclusion * 1000.;
-                    }
-                }
-            } else {
-                return conclusion * 1000.;
-            }
-        }
-
-        0.
+        Profile::find_conclusion(&self.hasObstacleTimeRules, node.get_tags())
+            .map(Conclusion::get_obstacle_time)
+            .unwrap_or(0.)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,42 @@
+use serde_json::{Number, Value};
+
+// the value of an `osm:` property of a node or way, as it was found in the tile
+#[derive(Debug, Clone, PartialEq)]
+pub enum Property {
+    String(String),
+    Number(Number), // keeps the distinction between 50 and 50.0
+    Bool(bool),
+    List(Vec<Property>),
+}
+
+impl Property {
+    // null and nested objects aren't supported
+    pub fn from_json(value: &Value) -> Option<Property> {
+        match value {
+            Value::String(value) => Some(Property::String(value.clone())),
+            Value::Number(value) => Some(Property::Number(value.clone())),
+            Value::Bool(value) => Some(Property::Bool(*value)),
+            Value::Array(values) => values.iter().map(Property::from_json).collect::<Option<_>>().map(Property::List),
+            Value::Null | Value::Object(_) => None,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        match self {
+            Property::String(value) => Value::String(value.clone()),
+            Property::Number(value) => Value::Number(value.clone()),
+            Property::Bool(value) => Value::Bool(*value),
+            Property::List(values) => Value::Array(values.iter().map(Property::to_json).collect()),
+        }
+    }
+
+    // the value that profiles match against, lists can't be matched
+    pub fn as_tag(&self) -> Option<String> {
+        match self {
+            Property::String(value) => Some(value.clone()),
+            Property::Number(value) => Some(value.to_string()),
+            Property::Bool(value) => Some(value.to_string()),
+            Property::List(_) => None,
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+use crate::entities::property::Property;
+use crate::entities::node::Node;
+use crate::entities::way::Way;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Member {
+    pub id: String,
+    pub role: String,
+}
+
+impl Member {
+    pub fn new(id: String, role: String) -> Member {
+        Member { id, role }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RestrictionKind {
+    Prohibitory, // e.g. no_left_turn
+    Mandatory,   // e.g. only_straight_on
+}
+
+// only restrictions with a single via node are supported, not the ones with via ways
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TurnRestriction<'a> {
+    pub from: &'a str, // way
+    pub via: &'a str,  // node
+    pub to: &'a str,   // way
+    pub kind: RestrictionKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relation {
+    id: String,
+    members: Vec<Member>,
+    tags: BTreeMap<String, String>,
+    undefined_tags: Vec<String>,
+    properties: BTreeMap<String, Property>, // the values that aren't plain strings
+}
+
+impl Relation {
+    pub fn new(
+        id: String,
+        members: Vec<Member>,
+        tags: BTreeMap<String, String>,
+        undefined_tags: Vec<String>,
+    ) -> Relation {
+        Relation {id, members, tags, undefined_tags, properties: BTreeMap::new()}
+    }
+
+    pub fn get_id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn get_members(&self) -> &[Member] {
+        &self.members
+    }
+
+    pub fn get_tags(&self) -> &BTreeMap<String, String> {
+        &self.tags
+    }
+
+    pub fn get_undefined_tags(&self) -> &[String] {
+        &self.undefined_tags
+    }
+
+    pub fn get_properties(&self) -> &BTreeMap<String, Property> {
+        &self.properties
+    }
+
+    pub fn set_properties(&mut self, properties: BTreeMap<String, Property>) {
+        self.properties = properties;
+    }
+
+    // members that are relations themselves don't count
+    pub fn touches(&self, nodes: &BTreeMap<String, Node>, ways: &BTreeMap<String, Way>) -> bool {
+        self.members
+            .iter()
+            .any(|member| nodes.contains_key(&member.id) || ways.contains_key(&member.id))
+    }
+
+    // the restriction that applies to the given transport modes, most specific first:
+    // `restriction:<mode>` wins over the plain tag, which only applies to vehicles
+    pub fn get_turn_restriction(&self, modes: &[&str]) -> Option<TurnRestriction<'_>> {
+        if self.tags.get("osm:type").map(String::as_str) != Some("restriction") {
+            return None;
+        }
+
+        if let Some(except) = self.tags.get("osm:except") {
+            if except.split(';').any(|mode| modes.contains(&mode.trim())) {
+                return None;
+            }
+        }
+
+        let value = modes
+            .iter()
+            .find_map(|mode| self.tags.get(&format!("osm:restriction:{}", mode)))
+            .or_else(|| self.tags.get("osm:restriction").filter(|_| modes.contains(&"vehicle")))?;
+        let kind = if value.starts_with("no_") {
+            RestrictionKind::Prohibitory
+        } else if value.starts_with("only_") {
+            RestrictionKind::Mandatory
+        } else {
+            return None;
+        };
+
+        // exactly one member per role
+        let member = |role: &str| {
+            let mut ids = self.members.iter().filter(|m| m.role == role).map(|m| m.id.as_str());
+            match (ids.next(), ids.next()) {
+                (Some(id), None) => Some(id),
+                _ => None,
+            }
+        };
+
+        Some(TurnRestriction {
+            from: member("from")?,
+            via: member("via")?,
+            to: member("to")?,
+            kind,
+        })
+    }
+}
@@ -1,14 +1,16 @@
 use std::collections::BTreeMap;
 use crate::entities::node::Node;
-use crate::entities::profile::Profile;
+use crate::entities::compiled_profile::{CompiledProfile, NodeProfileCache};
+use crate::entities::relation::{Relation, TurnRestriction};
 use crate::entities::segment::{Segment, WeightedSegment};
 use crate::entities::tile_coord::TileCoordinate;
 use crate::entities::way::Way;
 
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Tile {
     pub nodes: BTreeMap<String, Node>,
     pub ways: BTreeMap<String, Way>,
+    pub relations: BTreeMap<String, Relation>,
     pub coordinate: TileCoordinate,
 }
 
@@ -17,11 +19,13 @@ impl Tile {
         coordinate: TileCoordinate,
         nodes: BTreeMap<String, Node>,
         ways: BTreeMap<String, Way>,
+        relations: BTreeMap<String, Relation>,
     ) -> Tile {
         Tile {
             coordinate,
             nodes,
             ways,
+            relations,
         }
     }
 
@@ -37,30 +41,70 @@ impl Tile {
         &self.ways
     }
 
-    pub fn get_weighted_segments(&self, profile: &Profile) -> Vec<WeightedSegment> {
+    pub fn get_relations(&self) -> &BTreeMap<String, Relation> {
+        &self.relations
+    }
+
+    // the relations that still apply to a reduced version of this tile
+    pub fn get_touching_relations(
+        &self,
+        nodes: &BTreeMap<String, Node>,
+        ways: &BTreeMap<String, Way>,
+    ) -> BTreeMap<String, Relation> {
+        self.relations
+            .iter()
+            .filter(|(_, relation)| relation.touches(nodes, ways))
+            .map(|(id, relation)| (id.clone(), relation.clone()))
+            .collect()
+    }
+
+    pub fn get_turn_restrictions(&self, modes: &[&str]) -> Vec<TurnRestriction<'_>> {
+        self.relations
+            .values()
+            .filter_map(|relation| relation.get_turn_restriction(modes))
+            .collect()
+    }
+
+    pub fn get_weighted_segments(&self, profile: &CompiledProfile) -> Vec<WeightedSegment<'_>> {
+        self.get_weighted_way_segments(profile)
+            .into_iter()
+            .map(|(_, segment)| segment)
+            .collect()
+    }
+
+    // same as `get_weighted_segments`, along with the id of the way of each segment
+    pub fn get_weighted_way_segments(&self, profile: &CompiledProfile) -> Vec<(&str, WeightedSegment<'_>)> {
         let mut result = vec![];
+        let mut node_profiles = NodeProfileCache::new(profile);
         for way in self.get_ways().values() {
-            if !profile.has_access(way) {
+            let way_profile = profile.evaluate_way(way);
+            if !way_profile.access {
                 continue;
             }
 
+            let direction = way_profile.direction;
             for edge in way.get_segments() {
                 let Segment { from, to } = edge;
                 let from_node = self.get_nodes().get(from).expect("Corrupted tile");
                 let to_node = self.get_nodes().get(to).expect("Corrupted tile");
+                let from_profile = node_profiles.get(from_node);
+                let to_profile = node_profiles.get(to_node);
 
-                if profile.is_obstacle(from_node) || profile.is_obstacle(to_node) {
+                if from_profile.obstacle || to_profile.obstacle {
                     continue;
                 }
 
-                if !profile.is_one_way(way) {
-                    let backward_cost = profile.get_cost(to_node, from_node, way);
+                // costs are rounded to the nearest integer, the same way the weights of binary tiles are
+                if direction.allows_backward() {
+                    let backward_cost = profile.get_cost(to_node, from_node, &way_profile, &from_profile);
                     let reverse_edge = Segment::new(edge.to, edge.from);
-                    result.push(WeightedSegment::new(reverse_edge, backward_cost as u64));
+                    result.push((way.get_id(), WeightedSegment::new(reverse_edge, backward_cost.round() as u64)));
                 }
 
-                let forward_cost = profile.get_cost(from_node, to_node, way);
-                result.push(WeightedSegment::new(edge, forward_cost as u64));
+                if direction.allows_forward() {
+                    let forward_cost = profile.get_cost(from_node, to_node, &way_profile, &to_profile);
+                    result.push((way.get_id(), WeightedSegment::new(edge, forward_cost.round() as u64)));
+                }
             }
         }
         result
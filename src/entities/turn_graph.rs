@@ -0,0 +1,189 @@
+use crate::entities::relation::{RestrictionKind, TurnRestriction};
+use crate::entities::segment::WeightedSegment;
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+#[derive(Debug)]
+pub struct TurnEdge {
+    pub from: usize,
+    pub to: usize,
+    pub way: usize,
+    pub cost: i64,
+}
+
+// Edge-based counterpart of `Graph`: the search moves from edge to edge instead of from node to node,
+// so that turn restrictions can forbid going from one way to another.
+pub struct TurnGraph<'a> {
+    pub ids: Vec<&'a str>,
+    pub labels: BTreeMap<&'a str, usize>,
+    pub way_labels: BTreeMap<&'a str, usize>,
+    pub edges: Vec<TurnEdge>,
+    pub adj_list: Vec<Vec<usize>>, // outgoing edges of each node
+    forbidden: HashSet<(usize, usize, usize)>, // from way, via node, to way
+    mandatory: HashMap<(usize, usize), Vec<usize>>, // from way and via node to the allowed ways
+}
+
+impl<'a> TurnGraph<'a> {
+    pub fn new(
+        segments: Vec<(&'a str, WeightedSegment<'a>)>,
+        restrictions: &[TurnRestriction<'a>],
+    ) -> TurnGraph<'a> {
+        let mut graph = TurnGraph {
+            ids: vec![],
+            labels: BTreeMap::new(),
+            way_labels: BTreeMap::new(),
+            edges: vec![],
+            adj_list: vec![],
+            forbidden: HashSet::new(),
+            mandatory: HashMap::new(),
+        };
+        graph.add_edges(segments);
+        graph.add_restrictions(restrictions);
+        graph
+    }
+
+    pub fn add_edges(&mut self, segments: Vec<(&'a str, WeightedSegment<'a>)>) {
+        for (way, segment) in segments {
+            let from = self.get_label_mut(segment.segment.from);
+            let to = self.get_label_mut(segment.segment.to);
+            let way = self.get_way_label_mut(way);
+            self.adj_list[from].push(self.edges.len());
+            self.edges.push(TurnEdge {
+                from,
+                to,
+                way,
+                cost: segment.weight as i64,
+            });
+        }
+    }
+
+    pub fn add_restrictions(&mut self, restrictions: &[TurnRestriction<'a>]) {
+        for restriction in restrictions {
+            let from = self.get_way_label_mut(restriction.from);
+            let via = self.get_label_mut(restriction.via);
+            let to = self.get_way_label_mut(restriction.to);
+            match restriction.kind {
+                RestrictionKind::Prohibitory => {
+                    self.forbidden.insert((from, via, to));
+                }
+                RestrictionKind::Mandatory => self.mandatory.entry((from, via)).or_default().push(to),
+            }
+        }
+    }
+
+    pub fn get_label_mut(&mut self, id: &'a str) -> usize {
+        match self.labels.entry(id) {
+            Entry::Vacant(v) => {
+                let result = self.adj_list.len();
+                v.insert(result);
+                self.adj_list.push(Vec::new());
+                self.ids.push(id);
+                result
+            }
+            Entry::Occupied(ref label) => *label.get(),
+        }
+    }
+
+    pub fn get_way_label_mut(&mut self, id: &'a str) -> usize {
+        let next = self.way_labels.len();
+        *self.way_labels.entry(id).or_insert(next)
+    }
+
+    pub fn get_label(&self, id: &str) -> Option<&usize> {
+        self.labels.get(id)
+    }
+
+    pub fn allows_turn(&self, from: &TurnEdge, to: &TurnEdge) -> bool {
+        let via = from.to;
+        if self.forbidden.contains(&(from.way, via, to.way)) {
+            return false;
+        }
+        match self.mandatory.get(&(from.way, via)) {
+            Some(allowed) => allowed.contains(&to.way),
+            None => true,
+        }
+    }
+
+    // same as `Graph::necessary_nodes`, but only follows paths without forbidden turns
+    pub fn necessary_nodes(&self, from: &str, to: Vec<&String>) -> BTreeSet<String> {
+        let from_label = match self.get_label(from) {
+            Some(label) => *label,
+            None => return BTreeSet::new(),
+        };
+        let (previous, arrivals) = self.query_one_to_many(from_label, &to);
+
+        let mut used_nodes = BTreeSet::new();
+        for to_id in to {
+            let to_label = match self.get_label(to_id) {
+                Some(label) => *label,
+                None => continue,
+            };
+            used_nodes.insert(self.ids[from_label].to_owned());
+            used_nodes.insert(self.ids[to_label].to_owned());
+
+            let mut current_edge = match arrivals.get(&to_label) {
+                Some(edge) => *edge,
+                None => continue,
+            };
+            loop {
+                let edge = &self.edges[current_edge];
+                used_nodes.insert(self.ids[edge.from].to_owned());
+                if previous[current_edge] == current_edge {
+                    break;
+                }
+                current_edge = previous[current_edge];
+            }
+        }
+
+        used_nodes
+    }
+
+    // the previous edge of every reached edge, the first edges point to themselves,
+    // and the edge each of the targets was first reached with
+    fn query_one_to_many(&self, from_label: usize, to: &[&String]) -> (Vec<usize>, HashMap<usize, usize>) {
+        let mut to_labels: HashSet<usize> = to
+            .iter()
+            .filter_map(|id| self.get_label(id))
+            .copied()
+            .collect();
+        let mut dist = vec![std::i64::MIN; self.edges.len()];
+        let mut previous: Vec<usize> = (0..self.edges.len()).collect();
+        let mut arrivals = HashMap::new();
+        let mut queue = priority_queue::PriorityQueue::new();
+
+        for edge in &self.adj_list[from_label] {
+            let cost = -self.edges[*edge].cost;
+            if cost > dist[*edge] {
+                dist[*edge] = cost;
+                queue.push(*edge, cost);
+            }
+        }
+
+        while let Some((position, cost)) = queue.pop() {
+            let current = &self.edges[position];
+            if to_labels.remove(&current.to) {
+                arrivals.insert(current.to, position);
+            }
+
+            if to_labels.is_empty() {
+                break;
+            }
+
+            for next_position in &self.adj_list[current.to] {
+                let next = &self.edges[*next_position];
+                if !self.allows_turn(current, next) {
+                    continue;
+                }
+
+                let next_cost = cost - next.cost;
+                if next_cost > dist[*next_position] {
+                    queue.push(*next_position, next_cost);
+                    dist[*next_position] = next_cost;
+                    previous[*next_position] = position;
+                }
+            }
+        }
+
+        (previous, arrivals)
+    }
+}
@@ -1,7 +1,8 @@
 use std::collections::BTreeMap;
+use crate::entities::property::Property;
 use crate::entities::segment::Segment;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Way {
     id: String,
     nodes: Vec<String>,
@@ -9,6 +10,7 @@ pub struct Way {
     max_speed: Option<f64>,
     tags: BTreeMap<String, String>,
     undefined_tags: Vec<String>,
+    properties: BTreeMap<String, Property>, // the values that aren't plain strings
 }
 
 impl Way {
@@ -20,7 +22,7 @@ impl Way {
         tags: BTreeMap<String, String>,
         undefined_tags: Vec<String>,
     ) -> Way {
-        Way {id, nodes, distances, max_speed, tags, undefined_tags}
+        Way {id, nodes, distances, max_speed, tags, undefined_tags, properties: BTreeMap::new()}
     }
 
     pub fn get_id(&self) -> &str {
@@ -47,6 +49,14 @@ impl Way {
         &self.undefined_tags
     }
 
+    pub fn get_properties(&self) -> &BTreeMap<String, Property> {
+        &self.properties
+    }
+
+    pub fn set_properties(&mut self, properties: BTreeMap<String, Property>) {
+        self.properties = properties;
+    }
+
     pub fn get_segments(&self) -> Vec<Segment> {
         let mut result = vec!();
         for i in 0 .. self.get_nodes().len() - 1 {
@@ -1,28 +1,116 @@
 use std::collections::BTreeMap;
 use serde::{Serialize, Deserialize};
+use crate::entities::profile::ProfileSource;
+use crate::entities::tile_coord::TileCoordinate;
 
-#[derive(Serialize, Deserialize)]
+pub const MAGIC: &str = "routable-tiles/weighted-tile";
+// bump this whenever the layout of `WeightedTile` changes
+pub const SCHEMA_VERSION: u32 = 5;
+
+// what an edge weight expresses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Metric {
+    Cost,     // profile-weighted travel time
+    Duration, // travel time, including obstacles
+    Distance,
+}
+
+impl Metric {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Metric::Cost => "cost",
+            Metric::Duration => "duration",
+            Metric::Distance => "distance",
+        }
+    }
+
+    pub fn get_unit(&self) -> &'static str {
+        match self {
+            Metric::Cost | Metric::Duration => "ms",
+            Metric::Distance => "m",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TileHeader {
+    pub magic: String,
+    pub version: u32,
+    pub generator: String,
+    pub coordinate: TileCoordinate,
+    pub profile_name: String,
+    pub profile_hash: u64,
+    pub metrics: Vec<Metric>, // the first one is stored on the edges themselves
+    pub parameters: BTreeMap<String, String>,
+}
+
+impl TileHeader {
+    pub fn new(coordinate: TileCoordinate, profile: &ProfileSource) -> TileHeader {
+        TileHeader {
+            magic: MAGIC.to_owned(),
+            version: SCHEMA_VERSION,
+            generator: format!("preprocess {}", env!("CARGO_PKG_VERSION")),
+            coordinate,
+            profile_name: profile.name.clone(),
+            profile_hash: profile.hash,
+            metrics: vec![Metric::Cost],
+            parameters: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct WeightedTile {
+    pub header: TileHeader,
     pub locations: Vec<Location>,
     pub labels: BTreeMap<String, usize>,
     pub edges: Vec<DirectedEdge>,
+    pub way_references: Option<WayReferences>,
+    // weights of the other metrics in the header, one list per metric in the same order as `edges`
+    pub secondary_weights: Vec<Vec<u64>>,
+    // sorted by node
+    pub boundary_nodes: Vec<BoundaryNode>,
+    // sorted by the incoming edge, then by the outgoing one
+    pub forbidden_turns: Vec<ForbiddenTurn>,
 }
 
 impl WeightedTile {
     pub fn new(
-        locations: Vec<Location>, 
+        header: TileHeader,
+        locations: Vec<Location>,
         labels: BTreeMap<String, usize>,
         edges: Vec<DirectedEdge>
     ) -> WeightedTile {
         WeightedTile {
+            header,
             locations,
             labels,
             edges,
+            way_references: None,
+            secondary_weights: Vec::new(),
+            boundary_nodes: Vec::new(),
+            forbidden_turns: Vec::new(),
+        }
+    }
+
+    // the tile a node outside of this tile's bounds lies in
+    pub fn get_boundary_tile(&self, node: usize) -> Option<TileCoordinate> {
+        self.boundary_nodes
+            .binary_search_by_key(&node, |boundary| boundary.node)
+            .ok()
+            .map(|index| self.boundary_nodes[index].tile)
+    }
+
+    pub fn get_weight(&self, edge: usize, metric: Metric) -> Option<u64> {
+        match self.header.metrics.iter().position(|m| *m == metric)? {
+            0 => self.edges.get(edge).map(|edge| edge.weight),
+            index => self.secondary_weights.get(index - 1)?.get(edge).copied(),
         }
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct DirectedEdge {
     pub from: usize,
     pub to: usize,
@@ -36,9 +124,45 @@ impl DirectedEdge {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Location {
     pub lat: f64,
-    pub lon: f64, 
+    pub lon: f64,
+    pub id: String,
+}
+
+// a node that lies in another tile, adjacent tiles can be stitched together by joining on its id
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BoundaryNode {
+    pub node: usize,
+    pub tile: TileCoordinate,
+}
+
+// two edges that can't be followed one after the other because of a turn restriction,
+// the first one ends where the second one starts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ForbiddenTurn {
+    pub from: usize, // edge
+    pub to: usize,   // edge
+}
+
+// the way an edge was derived from, shared by all edges of that way
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WayReference {
     pub id: String,
+    pub tags: BTreeMap<String, String>,
+    pub length: f64, // metres, of the entire way
+}
+
+// kept apart from the edges so the edge array stays small when they're not needed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WayReferences {
+    pub ways: Vec<WayReference>,
+    pub edges: Vec<usize>, // index in `ways` for every edge
+}
+
+impl WayReferences {
+    pub fn get_way(&self, edge: usize) -> Option<&WayReference> {
+        self.edges.get(edge).and_then(|way| self.ways.get(*way))
+    }
 }
@@ -0,0 +1,114 @@
+use crate::entities::area::{Area, Polygon};
+use serde_json::Value;
+use std::fs;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone)]
+pub enum AreaError {
+    #[error("Invalid file path `{0}`")]
+    NotAFile(String),
+    #[error("Invalid JSON in `{0}`")]
+    NotJson(String),
+    #[error("Unsupported GeoJSON type `{0}`, expected a Polygon or MultiPolygon")]
+    UnsupportedType(String),
+    #[error("Invalid GeoJSON coordinates")]
+    InvalidCoordinates,
+    #[error("Position {0},{1} lies outside of the web mercator projection")]
+    OutOfBounds(f64, f64),
+    #[error("Area file `{0}` does not contain any polygons")]
+    Empty(String),
+}
+
+pub fn load_area_file(path: &str) -> Result<Area, AreaError> {
+    let data = match fs::read_to_string(path) {
+        Ok(data) => data,
+        _ => return Err(AreaError::NotAFile(path.to_owned())),
+    };
+
+    let v: Value = match serde_json::from_str(&data) {
+        Ok(v) => v,
+        Err(_) => return Err(AreaError::NotJson(path.to_owned())),
+    };
+
+    let mut polygons = Vec::new();
+    collect_polygons(&v, &mut polygons)?;
+
+    if polygons.is_empty() {
+        return Err(AreaError::Empty(path.to_owned()));
+    }
+
+    Ok(Area::Polygons(polygons))
+}
+
+fn collect_polygons(v: &Value, polygons: &mut Vec<Polygon>) -> Result<(), AreaError> {
+    let kind = v["type"].as_str().unwrap_or_default();
+    match kind {
+        "FeatureCollection" => {
+            if let Some(features) = v["features"].as_array() {
+                for feature in features {
+                    collect_polygons(feature, polygons)?;
+                }
+            }
+        }
+        "Feature" => collect_polygons(&v["geometry"], polygons)?,
+        "GeometryCollection" => {
+            if let Some(geometries) = v["geometries"].as_array() {
+                for geometry in geometries {
+                    collect_polygons(geometry, polygons)?;
+                }
+            }
+        }
+        "Polygon" => polygons.push(parse_polygon(&v["coordinates"])?),
+        "MultiPolygon" => {
+            let parts = v["coordinates"]
+                .as_array()
+                .ok_or(AreaError::InvalidCoordinates)?;
+            for part in parts {
+                polygons.push(parse_polygon(part)?);
+            }
+        }
+        other => return Err(AreaError::UnsupportedType(other.to_owned())),
+    }
+
+    Ok(())
+}
+
+fn parse_polygon(v: &Value) -> Result<Polygon, AreaError> {
+    let rings = v.as_array().ok_or(AreaError::InvalidCoordinates)?;
+    let mut rings: Vec<Vec<[f64; 2]>> = rings
+        .iter()
+        .map(parse_ring)
+        .collect::<Result<_, _>>()?;
+
+    if rings.is_empty() {
+        return Err(AreaError::InvalidCoordinates);
+    }
+
+    let exterior = rings.remove(0);
+    Ok(Polygon::new(exterior, rings))
+}
+
+fn parse_ring(v: &Value) -> Result<Vec<[f64; 2]>, AreaError> {
+    let positions = v.as_array().ok_or(AreaError::InvalidCoordinates)?;
+    let mut result = Vec::with_capacity(positions.len());
+    for position in positions {
+        let lon = position[0].as_f64().ok_or(AreaError::InvalidCoordinates)?;
+        let lat = position[1].as_f64().ok_or(AreaError::InvalidCoordinates)?;
+        // the same limits as a bounding box, tiles don't exist beyond them
+        if !(-85.0511..=85.0511).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+            return Err(AreaError::OutOfBounds(lon, lat));
+        }
+        result.push([lon, lat]);
+    }
+
+    // GeoJSON rings are closed, but don't rely on it
+    if result.len() > 1 && result.first() != result.last() {
+        result.push(result[0]);
+    }
+
+    if result.len() < 4 {
+        return Err(AreaError::InvalidCoordinates);
+    }
+
+    Ok(result)
+}
@@ -0,0 +1,496 @@
+use crate::entities::tile_coord::TileCoordinate;
+use crate::entities::weighted_tile::{
+    BoundaryNode, DirectedEdge, ForbiddenTurn, Location, TileHeader, WayReference, WayReferences, WeightedTile,
+};
+use crate::io::tiles::{check_binary_header, TileError};
+use crate::util::varint::{read_signed_varint, read_varint, write_signed_varint, write_varint};
+use flexbuffers::FlexbufferSerializer;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::fs;
+
+// Layout, all integers are varints and signed ones are zigzag encoded:
+//   magic, encoding version (1 byte)
+//   header: length + flexbuffer encoded `TileHeader`
+//   locations: id mode (1 byte), [id prefix], count, then per node the id and
+//              the fixed-point lat/lon, each as a delta to the previous node
+//   labels: mode (1 byte), explicit labels only if they can't be derived from the locations
+//   edges: out-degree per node, then all targets, then all weights, grouped by source node
+//   secondary weights: count, then all weights of each metric, in the same order as the edges
+//   boundary nodes: count, then per node the delta to the previous node
+//                   and its tile relative to the tile itself
+//   way references: mode (1 byte), [the referenced ways, then the way index of every edge]
+//   forbidden turns: count, then per turn the delta of the incoming edge to the previous one
+//                    and the delta of the outgoing edge to the incoming one
+pub const COMPACT_MAGIC: &[u8] = b"RTCT";
+// bump this whenever the byte layout changes
+const COMPACT_VERSION: u8 = 5;
+// OSM stores 7 decimals, so this is lossless for OSM coordinates
+const COORDINATE_SCALE: f64 = 1e7;
+
+const NUMERIC_IDS: u8 = 0;
+const STRING_IDS: u8 = 1;
+const DERIVED_LABELS: u8 = 0;
+const EXPLICIT_LABELS: u8 = 1;
+const NO_WAY_REFERENCES: u8 = 0;
+const WAY_REFERENCES: u8 = 1;
+
+pub fn is_compact_tile(data: &[u8]) -> bool {
+    data.len() > COMPACT_MAGIC.len() && data.starts_with(COMPACT_MAGIC)
+}
+
+pub fn write_compact_tile(tile: WeightedTile, path: &str) {
+    fs::write(path, encode_compact_tile(&tile)).expect("Unable to write file");
+}
+
+pub fn encode_compact_tile(tile: &WeightedTile) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(COMPACT_MAGIC);
+    out.push(COMPACT_VERSION);
+
+    let mut s = FlexbufferSerializer::new();
+    tile.header.serialize(&mut s).unwrap();
+    write_bytes(&mut out, s.view());
+
+    encode_locations(&mut out, &tile.locations);
+    encode_labels(&mut out, &tile.labels, &tile.locations);
+    let order = get_edge_order(&tile.edges);
+    encode_edges(&mut out, &tile.edges, &order, tile.locations.len());
+    encode_secondary_weights(&mut out, &tile.secondary_weights, &order);
+    encode_boundary_nodes(&mut out, &tile.boundary_nodes, &tile.header.coordinate);
+    encode_way_references(&mut out, tile.way_references.as_ref(), &order);
+    encode_forbidden_turns(&mut out, &reorder_turns(&tile.forbidden_turns, &order));
+    out
+}
+
+// edges come back grouped by source node, in their original order otherwise
+pub fn decode_compact_tile(path: &str, data: &[u8]) -> Result<WeightedTile, TileError> {
+    let invalid = || TileError::InvalidBinary(path.to_owned());
+    if !is_compact_tile(data) {
+        return Err(invalid());
+    }
+
+    let version = data[COMPACT_MAGIC.len()];
+    if version != COMPACT_VERSION {
+        let reason = format!("compact encoding version {}, expected {}", version, COMPACT_VERSION);
+        return Err(TileError::IncompatibleBinary(path.to_owned(), reason));
+    }
+
+    let mut position = COMPACT_MAGIC.len() + 1;
+    let header_data = read_bytes(data, &mut position).ok_or_else(invalid)?;
+    check_binary_header(path, header_data)?;
+    let header: TileHeader = flexbuffers::from_slice(header_data).map_err(|_| invalid())?;
+
+    let locations = decode_locations(data, &mut position).ok_or_else(invalid)?;
+    let labels = decode_labels(data, &mut position, &locations).ok_or_else(invalid)?;
+    let edges = decode_edges(data, &mut position, locations.len()).ok_or_else(invalid)?;
+    let secondary_weights = decode_secondary_weights(data, &mut position, edges.len()).ok_or_else(invalid)?;
+    let boundary_nodes =
+        decode_boundary_nodes(data, &mut position, &header.coordinate, locations.len()).ok_or_else(invalid)?;
+    let way_references = decode_way_references(data, &mut position, edges.len()).ok_or_else(invalid)?;
+    let forbidden_turns = decode_forbidden_turns(data, &mut position, edges.len()).ok_or_else(invalid)?;
+    if position != data.len() {
+        return Err(invalid());
+    }
+
+    let mut tile = WeightedTile::new(header, locations, labels, edges);
+    tile.secondary_weights = secondary_weights;
+    tile.boundary_nodes = boundary_nodes;
+    tile.way_references = way_references;
+    tile.forbidden_turns = forbidden_turns;
+    Ok(tile)
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+fn read_bytes<'a>(data: &'a [u8], position: &mut usize) -> Option<&'a [u8]> {
+    let length = read_varint(data, position)? as usize;
+    let end = position.checked_add(length)?;
+    let bytes = data.get(*position..end)?;
+    *position = end;
+    Some(bytes)
+}
+
+fn read_string(data: &[u8], position: &mut usize) -> Option<String> {
+    String::from_utf8(read_bytes(data, position)?.to_vec()).ok()
+}
+
+// every counted item takes at least one byte, anything larger is corrupt
+fn read_count(data: &[u8], position: &mut usize) -> Option<usize> {
+    let count = read_varint(data, position)? as usize;
+    if count > data.len() - *position {
+        return None;
+    }
+    Some(count)
+}
+
+fn read_byte(data: &[u8], position: &mut usize) -> Option<u8> {
+    let byte = *data.get(*position)?;
+    *position += 1;
+    Some(byte)
+}
+
+fn to_fixed(degrees: f64) -> i64 {
+    (degrees * COORDINATE_SCALE).round() as i64
+}
+
+fn from_fixed(value: i64) -> f64 {
+    value as f64 / COORDINATE_SCALE
+}
+
+// "http://www.openstreetmap.org/node/123" -> ("http://www.openstreetmap.org/node/", 123)
+pub fn split_numeric_id(id: &str) -> Option<(&str, u64)> {
+    let digits = id.bytes().rev().take_while(|b| b.is_ascii_digit()).count();
+    let (prefix, number) = id.split_at(id.len() - digits);
+    // leading zeros would not survive the round trip
+    if number.is_empty() || (number.len() > 1 && number.starts_with('0')) {
+        return None;
+    }
+    number.parse().ok().map(|number| (prefix, number))
+}
+
+// the shared prefix and the numbers, if all ids are numeric
+pub fn get_numeric_ids(locations: &[Location]) -> Option<(&str, Vec<u64>)> {
+    let mut prefix = None;
+    let mut numbers = Vec::with_capacity(locations.len());
+    for location in locations.iter() {
+        let (current, number) = split_numeric_id(&location.id)?;
+        if *prefix.get_or_insert(current) != current {
+            return None;
+        }
+        numbers.push(number);
+    }
+    Some((prefix.unwrap_or(""), numbers))
+}
+
+fn encode_locations(out: &mut Vec<u8>, locations: &[Location]) {
+    let numeric = get_numeric_ids(locations);
+    match numeric {
+        Some((prefix, _)) => {
+            out.push(NUMERIC_IDS);
+            write_bytes(out, prefix.as_bytes());
+        }
+        None => out.push(STRING_IDS),
+    }
+    write_varint(out, locations.len() as u64);
+
+    let (mut previous_id, mut previous_lat, mut previous_lon) = (0i64, 0i64, 0i64);
+    for (index, location) in locations.iter().enumerate() {
+        match numeric {
+            Some((_, ref numbers)) => {
+                let id = numbers[index] as i64;
+                write_signed_varint(out, id.wrapping_sub(previous_id));
+                previous_id = id;
+            }
+            None => write_bytes(out, location.id.as_bytes()),
+        }
+
+        let lat = to_fixed(location.lat);
+        let lon = to_fixed(location.lon);
+        write_signed_varint(out, lat - previous_lat);
+        write_signed_varint(out, lon - previous_lon);
+        previous_lat = lat;
+        previous_lon = lon;
+    }
+}
+
+fn decode_locations(data: &[u8], position: &mut usize) -> Option<Vec<Location>> {
+    let prefix = match read_byte(data, position)? {
+        NUMERIC_IDS => Some(read_string(data, position)?),
+        STRING_IDS => None,
+        _ => return None,
+    };
+    let count = read_count(data, position)?;
+
+    let mut locations = Vec::with_capacity(count);
+    let (mut previous_id, mut previous_lat, mut previous_lon) = (0i64, 0i64, 0i64);
+    for _ in 0..count {
+        let id = match prefix {
+            Some(ref prefix) => {
+                previous_id = previous_id.wrapping_add(read_signed_varint(data, position)?);
+                format!("{}{}", prefix, previous_id as u64)
+            }
+            None => read_string(data, position)?,
+        };
+        previous_lat = previous_lat.checked_add(read_signed_varint(data, position)?)?;
+        previous_lon = previous_lon.checked_add(read_signed_varint(data, position)?)?;
+
+        locations.push(Location {
+            id,
+            lat: from_fixed(previous_lat),
+            lon: from_fixed(previous_lon),
+        });
+    }
+    Some(locations)
+}
+
+fn encode_labels(out: &mut Vec<u8>, labels: &BTreeMap<String, usize>, locations: &[Location]) {
+    // `create_binary_tile` labels every location with its own index
+    let derived = labels.len() == locations.len()
+        && locations
+            .iter()
+            .enumerate()
+            .all(|(index, location)| labels.get(&location.id) == Some(&index));
+    if derived {
+        out.push(DERIVED_LABELS);
+        return;
+    }
+
+    out.push(EXPLICIT_LABELS);
+    write_varint(out, labels.len() as u64);
+    for (id, label) in labels.iter() {
+        write_bytes(out, id.as_bytes());
+        write_varint(out, *label as u64);
+    }
+}
+
+fn decode_labels(data: &[u8], position: &mut usize, locations: &[Location]) -> Option<BTreeMap<String, usize>> {
+    let mut labels = BTreeMap::new();
+    match read_byte(data, position)? {
+        DERIVED_LABELS => {
+            for (index, location) in locations.iter().enumerate() {
+                labels.insert(location.id.clone(), index);
+            }
+        }
+        EXPLICIT_LABELS => {
+            let count = read_count(data, position)?;
+            for _ in 0..count {
+                let id = read_string(data, position)?;
+                let label = read_varint(data, position)? as usize;
+                labels.insert(id, label);
+            }
+        }
+        _ => return None,
+    }
+    Some(labels)
+}
+
+// stable, so edges of the same node keep their order
+fn get_edge_order(edges: &[DirectedEdge]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..edges.len()).collect();
+    order.sort_by_key(|index| edges[*index].from);
+    order
+}
+
+fn encode_edges(out: &mut Vec<u8>, edges: &[DirectedEdge], order: &[usize], node_count: usize) {
+    let mut degrees = vec![0u64; node_count];
+    for edge in edges.iter() {
+        degrees[edge.from] += 1;
+    }
+
+    for degree in degrees {
+        write_varint(out, degree);
+    }
+    for index in order.iter() {
+        write_varint(out, edges[*index].to as u64);
+    }
+    for index in order.iter() {
+        write_varint(out, edges[*index].weight);
+    }
+}
+
+fn decode_edges(data: &[u8], position: &mut usize, node_count: usize) -> Option<Vec<DirectedEdge>> {
+    let mut degrees = Vec::with_capacity(node_count);
+    for _ in 0..node_count {
+        degrees.push(read_count(data, position)?);
+    }
+
+    let total: usize = degrees.iter().sum();
+    if total > data.len() - *position {
+        return None;
+    }
+
+    let mut targets = Vec::with_capacity(total);
+    for _ in 0..total {
+        let target = read_varint(data, position)? as usize;
+        if target >= node_count {
+            return None;
+        }
+        targets.push(target);
+    }
+
+    let mut edges = Vec::with_capacity(total);
+    let mut targets = targets.into_iter();
+    for (from, degree) in degrees.into_iter().enumerate() {
+        for _ in 0..degree {
+            let weight = read_varint(data, position)?;
+            edges.push(DirectedEdge::new(from, targets.next()?, weight));
+        }
+    }
+    Some(edges)
+}
+
+fn encode_secondary_weights(out: &mut Vec<u8>, secondary_weights: &[Vec<u64>], order: &[usize]) {
+    write_varint(out, secondary_weights.len() as u64);
+    for weights in secondary_weights.iter() {
+        for index in order.iter() {
+            write_varint(out, weights[*index]);
+        }
+    }
+}
+
+fn decode_secondary_weights(data: &[u8], position: &mut usize, edge_count: usize) -> Option<Vec<Vec<u64>>> {
+    let count = read_count(data, position)?;
+    let mut result = Vec::with_capacity(count);
+    for _ in 0..count {
+        let mut weights = Vec::with_capacity(edge_count);
+        for _ in 0..edge_count {
+            weights.push(read_varint(data, position)?);
+        }
+        result.push(weights);
+    }
+    Some(result)
+}
+
+fn encode_boundary_nodes(out: &mut Vec<u8>, boundary_nodes: &[BoundaryNode], coordinate: &TileCoordinate) {
+    write_varint(out, boundary_nodes.len() as u64);
+    let mut previous = 0;
+    for boundary in boundary_nodes.iter() {
+        write_signed_varint(out, boundary.node as i64 - previous as i64);
+        write_signed_varint(out, i64::from(boundary.tile.x) - i64::from(coordinate.x));
+        write_signed_varint(out, i64::from(boundary.tile.y) - i64::from(coordinate.y));
+        write_signed_varint(out, i64::from(boundary.tile.zoom) - i64::from(coordinate.zoom));
+        previous = boundary.node;
+    }
+}
+
+fn decode_boundary_nodes(
+    data: &[u8],
+    position: &mut usize,
+    coordinate: &TileCoordinate,
+    node_count: usize,
+) -> Option<Vec<BoundaryNode>> {
+    let relative = |base: u32, delta: i64| -> Option<u32> { i64::from(base).checked_add(delta)?.try_into().ok() };
+
+    let count = read_count(data, position)?;
+    let mut result = Vec::with_capacity(count);
+    let mut previous: i64 = 0;
+    for _ in 0..count {
+        previous = previous.checked_add(read_signed_varint(data, position)?)?;
+        let node: usize = previous.try_into().ok()?;
+        if node >= node_count {
+            return None;
+        }
+        let x = relative(coordinate.x, read_signed_varint(data, position)?)?;
+        let y = relative(coordinate.y, read_signed_varint(data, position)?)?;
+        let zoom = relative(coordinate.zoom, read_signed_varint(data, position)?)?;
+        result.push(BoundaryNode {
+            node,
+            tile: TileCoordinate::new(x, y, zoom),
+        });
+    }
+    Some(result)
+}
+
+fn encode_way_references(out: &mut Vec<u8>, references: Option<&WayReferences>, order: &[usize]) {
+    let references = match references {
+        Some(references) => references,
+        None => {
+            out.push(NO_WAY_REFERENCES);
+            return;
+        }
+    };
+
+    out.push(WAY_REFERENCES);
+    write_varint(out, references.ways.len() as u64);
+    for way in references.ways.iter() {
+        write_bytes(out, way.id.as_bytes());
+        write_varint(out, way.tags.len() as u64);
+        for (key, value) in way.tags.iter() {
+            write_bytes(out, key.as_bytes());
+            write_bytes(out, value.as_bytes());
+        }
+        out.extend_from_slice(&way.length.to_le_bytes());
+    }
+    for index in order.iter() {
+        write_varint(out, references.edges[*index] as u64);
+    }
+}
+
+fn decode_way_references(data: &[u8], position: &mut usize, edge_count: usize) -> Option<Option<WayReferences>> {
+    match read_byte(data, position)? {
+        NO_WAY_REFERENCES => return Some(None),
+        WAY_REFERENCES => {}
+        _ => return None,
+    }
+
+    let count = read_count(data, position)?;
+    let mut ways = Vec::with_capacity(count);
+    for _ in 0..count {
+        let id = read_string(data, position)?;
+        let mut tags = BTreeMap::new();
+        for _ in 0..read_count(data, position)? {
+            let key = read_string(data, position)?;
+            let value = read_string(data, position)?;
+            tags.insert(key, value);
+        }
+        let length = data.get(*position..*position + 8)?;
+        *position += 8;
+        let length = f64::from_le_bytes(length.try_into().ok()?);
+        ways.push(WayReference { id, tags, length });
+    }
+
+    let mut edges = Vec::with_capacity(edge_count);
+    for _ in 0..edge_count {
+        let way = read_varint(data, position)? as usize;
+        if way >= ways.len() {
+            return None;
+        }
+        edges.push(way);
+    }
+    Some(Some(WayReferences { ways, edges }))
+}
+
+// the turns in terms of the positions of the edges in the given order, sorted again
+pub fn reorder_turns(turns: &[ForbiddenTurn], order: &[usize]) -> Vec<ForbiddenTurn> {
+    let mut positions = vec![0; order.len()];
+    for (position, index) in order.iter().enumerate() {
+        positions[*index] = position;
+    }
+
+    let mut result: Vec<ForbiddenTurn> = turns
+        .iter()
+        .map(|turn| ForbiddenTurn {
+            from: positions[turn.from],
+            to: positions[turn.to],
+        })
+        .collect();
+    result.sort();
+    result
+}
+
+fn encode_forbidden_turns(out: &mut Vec<u8>, turns: &[ForbiddenTurn]) {
+    write_varint(out, turns.len() as u64);
+    let mut previous = 0;
+    for turn in turns.iter() {
+        write_signed_varint(out, turn.from as i64 - previous as i64);
+        write_signed_varint(out, turn.to as i64 - turn.from as i64);
+        previous = turn.from;
+    }
+}
+
+fn decode_forbidden_turns(data: &[u8], position: &mut usize, edge_count: usize) -> Option<Vec<ForbiddenTurn>> {
+    let edge = |base: i64, delta: i64| -> Option<usize> {
+        let edge: usize = base.checked_add(delta)?.try_into().ok()?;
+        if edge < edge_count {
+            Some(edge)
+        } else {
+            None
+        }
+    };
+
+    let count = read_count(data, position)?;
+    let mut result = Vec::with_capacity(count);
+    let mut previous = 0;
+    for _ in 0..count {
+        let from = edge(previous as i64, read_signed_varint(data, position)?)?;
+        let to = edge(from as i64, read_signed_varint(data, position)?)?;
+        result.push(ForbiddenTurn { from, to });
+        previous = from;
+    }
+    Some(result)
+}
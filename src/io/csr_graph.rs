@@ -0,0 +1,466 @@
+use crate::entities::tile_coord::TileCoordinate;
+use crate::entities::weighted_tile::{BoundaryNode, DirectedEdge, ForbiddenTurn, Location, TileHeader, WeightedTile};
+use crate::io::compact_tile::{get_numeric_ids, reorder_turns, split_numeric_id};
+use flexbuffers::FlexbufferSerializer;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::fs;
+use thiserror::Error;
+
+// Fixed layout, little-endian, every section starts at a multiple of 8 bytes
+// so the file can be mapped into memory and used as is:
+//   0   magic (8 bytes)
+//   8   u32 version, u32 metric count
+//   16  u64 node count, u64 edge count
+//   32  u64 start of each section: offsets, targets, weights, ids, coordinates
+//   72  u64 start and length of the node id prefix and of the flexbuffer encoded `TileHeader`
+//   104 u64 start and count of the boundary nodes
+//   120 u64 start and count of the forbidden turns
+//   136 sections:
+//       offsets      u32 x (nodes + 1), edges of node i are offsets[i]..offsets[i + 1]
+//       targets      u32 x edges
+//       weights      u64 x edges x metrics, all edges for the first metric, then for the second, ...
+//       ids          u64 x nodes, numeric part of the node ids
+//       coordinates  i32 x 2 x nodes, lat/lon in 1e-7 degrees
+//       boundary     u32 x 4 x boundary nodes, node and x/y/zoom of the tile it lies in, sorted by node
+//       turns        u32 x 2 x forbidden turns, incoming and outgoing edge, sorted
+// Way references are left out, routing doesn't need them.
+pub const CSR_MAGIC: &[u8] = b"RTCSR\0\0\0";
+// bump this whenever the byte layout changes
+pub const CSR_VERSION: u32 = 4;
+const HEADER_SIZE: usize = 136;
+const COORDINATE_SCALE: f64 = 1e7;
+
+#[derive(Error, Debug, Clone)]
+pub enum CsrError {
+    #[error("Not a CSR graph")]
+    NotACsrGraph,
+    #[error("Unsupported CSR graph version {0}")]
+    UnsupportedVersion(u32),
+    #[error("Corrupted CSR graph: {0}")]
+    Corrupted(&'static str),
+    #[error("Node id `{0}` can't be stored in a CSR graph, ids need a shared prefix and a numeric suffix")]
+    NonNumericId(String),
+    #[error("Tile too large for a CSR graph")]
+    TooLarge,
+}
+
+pub fn is_csr_graph(data: &[u8]) -> bool {
+    data.starts_with(CSR_MAGIC)
+}
+
+pub fn write_csr_tile(tile: WeightedTile, path: &str) -> Result<(), CsrError> {
+    let data = encode_csr_graph(&tile)?;
+    fs::write(path, data).expect("Unable to write file");
+    Ok(())
+}
+
+fn pad(out: &mut Vec<u8>) -> usize {
+    while out.len() % 8 != 0 {
+        out.push(0);
+    }
+    out.len()
+}
+
+fn to_u32(value: usize) -> Result<u32, CsrError> {
+    value.try_into().map_err(|_| CsrError::TooLarge)
+}
+
+pub fn encode_csr_graph(tile: &WeightedTile) -> Result<Vec<u8>, CsrError> {
+    let node_count = tile.locations.len();
+    let (prefix, ids) = match get_numeric_ids(&tile.locations) {
+        Some(numeric) => numeric,
+        None => {
+            // report the first id that doesn't fit
+            let expected = tile
+                .locations
+                .first()
+                .and_then(|location| split_numeric_id(&location.id))
+                .map(|(prefix, _)| prefix);
+            let id = tile
+                .locations
+                .iter()
+                .map(|location| &location.id)
+                .find(|id| expected.is_none() || split_numeric_id(id).map(|(prefix, _)| prefix) != expected)
+                .cloned()
+                .unwrap_or_default();
+            return Err(CsrError::NonNumericId(id));
+        }
+    };
+
+    // stable, so edges of the same node keep their order
+    let mut order: Vec<usize> = (0..tile.edges.len()).collect();
+    order.sort_by_key(|index| tile.edges[*index].from);
+    let sorted: Vec<&DirectedEdge> = order.iter().map(|index| &tile.edges[*index]).collect();
+
+    let mut out = vec![0; HEADER_SIZE];
+
+    let offsets_start = pad(&mut out);
+    let mut offset = 0;
+    let mut edges = sorted.iter().peekable();
+    for node in 0..=node_count {
+        out.extend_from_slice(&to_u32(offset)?.to_le_bytes());
+        while edges.peek().map_or(false, |edge| edge.from == node) {
+            edges.next();
+            offset += 1;
+        }
+    }
+
+    let targets_start = pad(&mut out);
+    for edge in sorted.iter() {
+        out.extend_from_slice(&to_u32(edge.to)?.to_le_bytes());
+    }
+
+    let weights_start = pad(&mut out);
+    for edge in sorted.iter() {
+        out.extend_from_slice(&edge.weight.to_le_bytes());
+    }
+    for weights in tile.secondary_weights.iter() {
+        for index in order.iter() {
+            out.extend_from_slice(&weights[*index].to_le_bytes());
+        }
+    }
+
+    let ids_start = pad(&mut out);
+    for id in ids.iter() {
+        out.extend_from_slice(&id.to_le_bytes());
+    }
+
+    let coordinates_start = pad(&mut out);
+    for location in tile.locations.iter() {
+        let lat = (location.lat * COORDINATE_SCALE).round() as i32;
+        let lon = (location.lon * COORDINATE_SCALE).round() as i32;
+        out.extend_from_slice(&lat.to_le_bytes());
+        out.extend_from_slice(&lon.to_le_bytes());
+    }
+
+    let boundary_start = pad(&mut out);
+    for boundary in tile.boundary_nodes.iter() {
+        out.extend_from_slice(&to_u32(boundary.node)?.to_le_bytes());
+        out.extend_from_slice(&boundary.tile.x.to_le_bytes());
+        out.extend_from_slice(&boundary.tile.y.to_le_bytes());
+        out.extend_from_slice(&boundary.tile.zoom.to_le_bytes());
+    }
+
+    let turns = reorder_turns(&tile.forbidden_turns, &order);
+    let turns_start = pad(&mut out);
+    for turn in turns.iter() {
+        out.extend_from_slice(&to_u32(turn.from)?.to_le_bytes());
+        out.extend_from_slice(&to_u32(turn.to)?.to_le_bytes());
+    }
+
+    let prefix_start = pad(&mut out);
+    out.extend_from_slice(prefix.as_bytes());
+
+    let header_start = pad(&mut out);
+    // the way references are left out, so the header can't advertise them
+    let mut header = tile.header.clone();
+    header.parameters.remove("way_references");
+    let mut s = FlexbufferSerializer::new();
+    header.serialize(&mut s).unwrap();
+    out.extend_from_slice(s.view());
+    pad(&mut out);
+
+    let fields = [
+        node_count,
+        sorted.len(),
+        offsets_start,
+        targets_start,
+        weights_start,
+        ids_start,
+        coordinates_start,
+        prefix_start,
+        prefix.len(),
+        header_start,
+        s.view().len(),
+        boundary_start,
+        tile.boundary_nodes.len(),
+        turns_start,
+        turns.len(),
+    ];
+    out[..8].copy_from_slice(CSR_MAGIC);
+    out[8..12].copy_from_slice(&CSR_VERSION.to_le_bytes());
+    out[12..16].copy_from_slice(&to_u32(tile.secondary_weights.len() + 1)?.to_le_bytes());
+    for (index, field) in fields.iter().enumerate() {
+        let position = 16 + index * 8;
+        out[position..position + 8].copy_from_slice(&(*field as u64).to_le_bytes());
+    }
+
+    Ok(out)
+}
+
+fn read_u32(data: &[u8], position: usize) -> u32 {
+    u32::from_le_bytes(data[position..position + 4].try_into().unwrap())
+}
+
+fn read_i32(data: &[u8], position: usize) -> i32 {
+    i32::from_le_bytes(data[position..position + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], position: usize) -> u64 {
+    u64::from_le_bytes(data[position..position + 8].try_into().unwrap())
+}
+
+// A read-only view over an encoded graph, e.g. a memory mapped file.
+// Everything is validated once up front, queries never allocate.
+#[derive(Debug, Clone, Copy)]
+pub struct CsrGraph<'a> {
+    data: &'a [u8],
+    node_count: usize,
+    edge_count: usize,
+    metric_count: usize,
+    offsets: usize,
+    targets: usize,
+    weights: usize,
+    ids: usize,
+    coordinates: usize,
+    boundary: usize,
+    boundary_count: usize,
+    turns: usize,
+    turn_count: usize,
+    prefix: &'a str,
+    header: &'a [u8],
+}
+
+impl<'a> CsrGraph<'a> {
+    pub fn new(data: &'a [u8]) -> Result<CsrGraph<'a>, CsrError> {
+        if data.len() < HEADER_SIZE || !is_csr_graph(data) {
+            return Err(CsrError::NotACsrGraph);
+        }
+        let version = read_u32(data, 8);
+        if version != CSR_VERSION {
+            return Err(CsrError::UnsupportedVersion(version));
+        }
+
+        let field = |index: usize| read_u64(data, 16 + index * 8) as usize;
+        let node_count = field(0);
+        let edge_count = field(1);
+        let metric_count = read_u32(data, 12) as usize;
+        if metric_count == 0 {
+            return Err(CsrError::Corrupted("metric count"));
+        }
+
+        // checks that a section is aligned and fits in the data
+        let section = |index: usize, length: Option<usize>, name: &'static str| {
+            let start = field(index);
+            let end = length.and_then(|length| start.checked_add(length));
+            match end {
+                Some(end) if start % 8 == 0 && start >= HEADER_SIZE && end <= data.len() => Ok(start),
+                _ => Err(CsrError::Corrupted(name)),
+            }
+        };
+        let offsets = section(2, node_count.checked_add(1).and_then(|n| n.checked_mul(4)), "offsets")?;
+        let targets = section(3, edge_count.checked_mul(4), "targets")?;
+        let weights = section(4, edge_count.checked_mul(8 * metric_count), "weights")?;
+        let ids = section(5, node_count.checked_mul(8), "ids")?;
+        let coordinates = section(6, node_count.checked_mul(8), "coordinates")?;
+        let prefix_start = section(7, Some(field(8)), "prefix")?;
+        let header_start = section(9, Some(field(10)), "header")?;
+        let boundary_count = field(12);
+        let boundary = section(11, boundary_count.checked_mul(16), "boundary")?;
+        let turn_count = field(14);
+        let turns = section(13, turn_count.checked_mul(8), "turns")?;
+
+        let prefix = std::str::from_utf8(&data[prefix_start..prefix_start + field(8)])
+            .map_err(|_| CsrError::Corrupted("prefix"))?;
+        let header = &data[header_start..header_start + field(10)];
+
+        // offsets must be increasing and targets must exist, so queries can't go out of bounds
+        let mut previous = 0;
+        for node in 0..=node_count {
+            let offset = read_u32(data, offsets + node * 4) as usize;
+            if offset < previous || (node == 0 && offset != 0) {
+                return Err(CsrError::Corrupted("offsets"));
+            }
+            previous = offset;
+        }
+        if previous != edge_count {
+            return Err(CsrError::Corrupted("offsets"));
+        }
+        for edge in 0..edge_count {
+            if read_u32(data, targets + edge * 4) as usize >= node_count {
+                return Err(CsrError::Corrupted("targets"));
+            }
+        }
+        // sorted, so they can be binary searched
+        for index in 0..boundary_count {
+            let node = read_u32(data, boundary + index * 16) as usize;
+            let sorted = index == 0 || (read_u32(data, boundary + (index - 1) * 16) as usize) < node;
+            if node >= node_count || !sorted {
+                return Err(CsrError::Corrupted("boundary"));
+            }
+        }
+        let mut previous = None;
+        for index in 0..turn_count {
+            let turn = (read_u32(data, turns + index * 8), read_u32(data, turns + index * 8 + 4));
+            let sorted = previous.map_or(true, |previous| previous < turn);
+            if turn.0 as usize >= edge_count || turn.1 as usize >= edge_count || !sorted {
+                return Err(CsrError::Corrupted("turns"));
+            }
+            previous = Some(turn);
+        }
+
+        Ok(CsrGraph {
+            data,
+            node_count,
+            edge_count,
+            metric_count,
+            offsets,
+            targets,
+            weights,
+            ids,
+            coordinates,
+            boundary,
+            boundary_count,
+            turns,
+            turn_count,
+            prefix,
+            header,
+        })
+    }
+
+    pub fn get_node_count(&self) -> usize {
+        self.node_count
+    }
+
+    pub fn get_edge_count(&self) -> usize {
+        self.edge_count
+    }
+
+    // the metrics themselves are listed in the header
+    pub fn get_metric_count(&self) -> usize {
+        self.metric_count
+    }
+
+    // panics if the node doesn't exist, like slice indexing
+    pub fn get_neighbours(&self, node: usize) -> Neighbours<'a> {
+        self.get_metric_neighbours(node, 0)
+    }
+
+    // same as `get_neighbours`, with the weights of the given metric
+    pub fn get_metric_neighbours(&self, node: usize, metric: usize) -> Neighbours<'a> {
+        assert!(metric < self.metric_count, "metric {} out of bounds", metric);
+        let (start, end) = self.get_edge_range(node);
+        Neighbours {
+            graph: *self,
+            weights: self.weights + metric * self.edge_count * 8,
+            current: start,
+            end,
+        }
+    }
+
+    pub fn get_node_id(&self, node: usize) -> u64 {
+        assert!(node < self.node_count, "node {} out of bounds", node);
+        read_u64(self.data, self.ids + node * 8)
+    }
+
+    // e.g. "http://www.openstreetmap.org/node/", shared by all node ids
+    pub fn get_id_prefix(&self) -> &'a str {
+        self.prefix
+    }
+
+    // [lat, lon]
+    pub fn get_location(&self, node: usize) -> [f64; 2] {
+        assert!(node < self.node_count, "node {} out of bounds", node);
+        let position = self.coordinates + node * 8;
+        let lat = read_i32(self.data, position);
+        let lon = read_i32(self.data, position + 4);
+        [f64::from(lat) / COORDINATE_SCALE, f64::from(lon) / COORDINATE_SCALE]
+    }
+
+    // the tile a node outside of this tile's bounds lies in
+    fn get_boundary_entry(&self, index: usize) -> (usize, TileCoordinate) {
+        let position = self.boundary + index * 16;
+        let node = read_u32(self.data, position) as usize;
+        let x = read_u32(self.data, position + 4);
+        let y = read_u32(self.data, position + 8);
+        let zoom = read_u32(self.data, position + 12);
+        (node, TileCoordinate::new(x, y, zoom))
+    }
+
+    // pairs of edges, by their position in the targets and weights sections
+    pub fn get_forbidden_turns(&self) -> impl Iterator<Item = ForbiddenTurn> + 'a {
+        let (data, turns) = (self.data, self.turns);
+        (0..self.turn_count).map(move |index| ForbiddenTurn {
+            from: read_u32(data, turns + index * 8) as usize,
+            to: read_u32(data, turns + index * 8 + 4) as usize,
+        })
+    }
+
+    // the flexbuffer encoded header, see `check_binary_header`
+    pub fn get_raw_header(&self) -> &'a [u8] {
+        self.header
+    }
+
+    fn get_edge_range(&self, node: usize) -> (usize, usize) {
+        assert!(node < self.node_count, "node {} out of bounds", node);
+        let start = read_u32(self.data, self.offsets + node * 4) as usize;
+        let end = read_u32(self.data, self.offsets + (node + 1) * 4) as usize;
+        (start, end)
+    }
+
+    // copies everything into the regular heap representation
+    pub fn to_weighted_tile(self, header: TileHeader) -> WeightedTile {
+        let mut locations = Vec::with_capacity(self.get_node_count());
+        let mut labels = BTreeMap::new();
+        let mut edges = Vec::with_capacity(self.get_edge_count());
+        let mut secondary_weights = vec![Vec::with_capacity(self.get_edge_count()); self.get_metric_count() - 1];
+
+        for node in 0..self.get_node_count() {
+            let id = format!("{}{}", self.get_id_prefix(), self.get_node_id(node));
+            let [lat, lon] = self.get_location(node);
+            labels.insert(id.clone(), node);
+            locations.push(Location { id, lat, lon });
+
+            for (to, weight) in self.get_neighbours(node) {
+                edges.push(DirectedEdge::new(node, to, weight));
+            }
+            for (metric, weights) in secondary_weights.iter_mut().enumerate() {
+                weights.extend(self.get_metric_neighbours(node, metric + 1).map(|(_, weight)| weight));
+            }
+        }
+
+        let mut tile = WeightedTile::new(header, locations, labels, edges);
+        tile.secondary_weights = secondary_weights;
+        tile.boundary_nodes = (0..self.boundary_count)
+            .map(|index| {
+                let (node, tile) = self.get_boundary_entry(index);
+                BoundaryNode { node, tile }
+            })
+            .collect();
+        tile.forbidden_turns = self.get_forbidden_turns().collect();
+        tile
+    }
+}
+
+// (target node, weight) of the outgoing edges of a node
+pub struct Neighbours<'a> {
+    graph: CsrGraph<'a>,
+    weights: usize,
+    current: usize,
+    end: usize,
+}
+
+impl<'a> Iterator for Neighbours<'a> {
+    type Item = (usize, u64);
+
+    fn next(&mut self) -> Option<(usize, u64)> {
+        if self.current >= self.end {
+            return None;
+        }
+        let edge = self.current;
+        self.current += 1;
+
+        let target = read_u32(self.graph.data, self.graph.targets + edge * 4) as usize;
+        let weight = read_u64(self.graph.data, self.weights + edge * 8);
+        Some((target, weight))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.end - self.current;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for Neighbours<'a> {}
@@ -0,0 +1,691 @@
+use crate::util::varint::{read_varint, zigzag_decode};
+use flate2::read::ZlibDecoder;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, ErrorKind, Read};
+use std::str::FromStr;
+use thiserror::Error;
+
+// limits from the PBF specification, anything larger is a corrupted file
+const MAX_HEADER_SIZE: usize = 64 * 1024;
+const MAX_BLOB_SIZE: usize = 32 * 1024 * 1024;
+
+const SUPPORTED_FEATURES: &[&str] = &["OsmSchema-V0.6", "DenseNodes"];
+
+#[derive(Error, Debug)]
+pub enum ExtractError {
+    #[error("Invalid file path `{0}`")]
+    NotAFile(String),
+    #[error("Unknown extract format `{0}`, expected a .osm.pbf or .osm file")]
+    UnknownFormat(String),
+    #[error("Invalid PBF file: {0}")]
+    InvalidPbf(String),
+    #[error("Unsupported PBF file: {0}")]
+    UnsupportedPbf(String),
+    #[error("Invalid OSM XML file: {0}")]
+    InvalidXml(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MemberType {
+    Node,
+    Way,
+    Relation,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OsmNode {
+    pub id: i64,
+    pub lat: f64,
+    pub lon: f64,
+    pub tags: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OsmWay {
+    pub id: i64,
+    pub nodes: Vec<i64>,
+    pub tags: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OsmMember {
+    pub kind: MemberType,
+    pub id: i64,
+    pub role: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OsmRelation {
+    pub id: i64,
+    pub members: Vec<OsmMember>,
+    pub tags: Vec<(String, String)>,
+}
+
+// the raw OpenStreetMap data model, as found in extracts
+#[derive(Debug, Clone, PartialEq)]
+pub enum OsmElement {
+    Node(OsmNode),
+    Way(OsmWay),
+    Relation(OsmRelation),
+}
+
+impl OsmElement {
+    fn get_tags_mut(&mut self) -> &mut Vec<(String, String)> {
+        match self {
+            OsmElement::Node(node) => &mut node.tags,
+            OsmElement::Way(way) => &mut way.tags,
+            OsmElement::Relation(relation) => &mut relation.tags,
+        }
+    }
+}
+
+// streams the elements of a .osm.pbf or .osm file, in the order they're stored
+pub fn read_extract(path: &str, visit: &mut dyn FnMut(OsmElement)) -> Result<(), ExtractError> {
+    let is_pbf = path.ends_with(".pbf");
+    if !is_pbf && !path.ends_with(".osm") && !path.ends_with(".xml") {
+        return Err(ExtractError::UnknownFormat(path.to_owned()));
+    }
+
+    let file = File::open(path).map_err(|_| ExtractError::NotAFile(path.to_owned()))?;
+    if is_pbf {
+        read_pbf(BufReader::new(file), visit)
+    } else {
+        read_xml(BufReader::new(file), visit)
+    }
+}
+
+fn invalid_pbf<T: ToString>(reason: T) -> ExtractError {
+    ExtractError::InvalidPbf(reason.to_string())
+}
+
+// a protobuf field, fixed-size values aren't used by the OSM messages
+enum Field<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]), // strings, messages and packed lists
+    Fixed,
+}
+
+struct Message<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Message<'a> {
+    fn new(data: &'a [u8]) -> Message<'a> {
+        Message { data, position: 0 }
+    }
+
+    fn next_field(&mut self) -> Result<Option<(u64, Field<'a>)>, ExtractError> {
+        if self.position >= self.data.len() {
+            return Ok(None);
+        }
+
+        let key = self.read_varint()?;
+        let field = match key & 7 {
+            0 => Field::Varint(self.read_varint()?),
+            1 => {
+                self.skip(8)?;
+                Field::Fixed
+            }
+            2 => {
+                let length = self.read_varint()? as usize;
+                let start = self.position;
+                self.skip(length)?;
+                let data = self.data;
+                Field::Bytes(&data[start..self.position])
+            }
+            5 => {
+                self.skip(4)?;
+                Field::Fixed
+            }
+            wire_type => return Err(invalid_pbf(format!("unsupported wire type {}", wire_type))),
+        };
+        Ok(Some((key >> 3, field)))
+    }
+
+    fn read_varint(&mut self) -> Result<u64, ExtractError> {
+        read_varint(self.data, &mut self.position).ok_or_else(|| invalid_pbf("truncated varint"))
+    }
+
+    fn skip(&mut self, length: usize) -> Result<(), ExtractError> {
+        if length > self.data.len() - self.position {
+            return Err(invalid_pbf("truncated field"));
+        }
+        self.position += length;
+        Ok(())
+    }
+}
+
+// repeated numbers are usually packed, but they may also be written one by one
+fn read_numbers(field: Field, out: &mut Vec<u64>) -> Result<(), ExtractError> {
+    match field {
+        Field::Varint(value) => out.push(value),
+        Field::Bytes(data) => {
+            let mut position = 0;
+            while position < data.len() {
+                out.push(read_varint(data, &mut position).ok_or_else(|| invalid_pbf("truncated packed list"))?);
+            }
+        }
+        Field::Fixed => return Err(invalid_pbf("unexpected fixed-size value")),
+    }
+    Ok(())
+}
+
+// delta coded, zigzag encoded values
+fn decode_deltas(values: &[u64]) -> Vec<i64> {
+    let mut current = 0;
+    values
+        .iter()
+        .map(|value| {
+            current += zigzag_decode(*value);
+            current
+        })
+        .collect()
+}
+
+fn read_bytes<R: Read>(reader: &mut R, length: usize) -> Result<Vec<u8>, ExtractError> {
+    let mut buffer = vec![0; length];
+    reader.read_exact(&mut buffer).map_err(invalid_pbf)?;
+    Ok(buffer)
+}
+
+fn read_pbf<R: Read>(mut reader: R, visit: &mut dyn FnMut(OsmElement)) -> Result<(), ExtractError> {
+    loop {
+        let mut length = [0; 4];
+        match reader.read_exact(&mut length) {
+            Ok(()) => {}
+            Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(invalid_pbf(e)),
+        }
+
+        let length = u32::from_be_bytes(length) as usize;
+        if length > MAX_HEADER_SIZE {
+            return Err(invalid_pbf(format!("blob header of {} bytes", length)));
+        }
+        let header = read_bytes(&mut reader, length)?;
+
+        let mut kind = String::new();
+        let mut size = 0;
+        let mut message = Message::new(&header);
+        while let Some((number, field)) = message.next_field()? {
+            match (number, field) {
+                (1, Field::Bytes(value)) => kind = String::from_utf8_lossy(value).into_owned(),
+                (3, Field::Varint(value)) => size = value as usize,
+                _ => {}
+            }
+        }
+        if size > MAX_BLOB_SIZE {
+            return Err(invalid_pbf(format!("blob of {} bytes", size)));
+        }
+        let blob = read_bytes(&mut reader, size)?;
+
+        // other kinds of blobs can be skipped
+        match kind.as_str() {
+            "OSMHeader" => check_header(&read_blob(&blob)?)?,
+            "OSMData" => read_primitive_block(&read_blob(&blob)?, visit)?,
+            _ => {}
+        }
+    }
+}
+
+fn read_blob(blob: &[u8]) -> Result<Vec<u8>, ExtractError> {
+    let mut raw_size = 0;
+    let mut raw = None;
+    let mut compressed = None;
+    let mut message = Message::new(blob);
+    while let Some((number, field)) = message.next_field()? {
+        match (number, field) {
+            (1, Field::Bytes(data)) => raw = Some(data),
+            (2, Field::Varint(size)) => raw_size = size as usize,
+            (3, Field::Bytes(data)) => compressed = Some(data),
+            (4, _) => return Err(ExtractError::UnsupportedPbf("lzma compression".to_owned())),
+            (6, _) => return Err(ExtractError::UnsupportedPbf("lz4 compression".to_owned())),
+            (7, _) => return Err(ExtractError::UnsupportedPbf("zstd compression".to_owned())),
+            _ => {}
+        }
+    }
+
+    match (raw, compressed) {
+        (Some(data), _) => Ok(data.to_vec()),
+        (None, Some(data)) => {
+            let mut result = Vec::with_capacity(raw_size.min(MAX_BLOB_SIZE));
+            ZlibDecoder::new(data).read_to_end(&mut result).map_err(invalid_pbf)?;
+            Ok(result)
+        }
+        (None, None) => Err(invalid_pbf("blob without data")),
+    }
+}
+
+fn check_header(data: &[u8]) -> Result<(), ExtractError> {
+    let mut message = Message::new(data);
+    while let Some((number, field)) = message.next_field()? {
+        if let (4, Field::Bytes(feature)) = (number, field) {
+            let feature = String::from_utf8_lossy(feature).into_owned();
+            if !SUPPORTED_FEATURES.contains(&feature.as_str()) {
+                return Err(ExtractError::UnsupportedPbf(format!("required feature {}", feature)));
+            }
+        }
+    }
+    Ok(())
+}
+
+struct PrimitiveBlock {
+    strings: Vec<String>,
+    granularity: i64,
+    lat_offset: i64,
+    lon_offset: i64,
+}
+
+impl PrimitiveBlock {
+    fn get_string(&self, index: u64) -> Result<String, ExtractError> {
+        self.strings
+            .get(index as usize)
+            .cloned()
+            .ok_or_else(|| invalid_pbf(format!("string {} is out of bounds", index)))
+    }
+
+    fn get_tags(&self, keys: &[u64], values: &[u64]) -> Result<Vec<(String, String)>, ExtractError> {
+        if keys.len() != values.len() {
+            return Err(invalid_pbf("tags with a different number of keys and values"));
+        }
+        keys.iter()
+            .zip(values.iter())
+            .map(|(key, value)| Ok((self.get_string(*key)?, self.get_string(*value)?)))
+            .collect()
+    }
+
+    fn get_lat(&self, value: i64) -> f64 {
+        (self.lat_offset + self.granularity * value) as f64 * 1e-9
+    }
+
+    fn get_lon(&self, value: i64) -> f64 {
+        (self.lon_offset + self.granularity * value) as f64 * 1e-9
+    }
+}
+
+fn read_primitive_block(data: &[u8], visit: &mut dyn FnMut(OsmElement)) -> Result<(), ExtractError> {
+    let mut block = PrimitiveBlock {
+        strings: Vec::new(),
+        granularity: 100,
+        lat_offset: 0,
+        lon_offset: 0,
+    };
+    let mut groups = Vec::new();
+    let mut message = Message::new(data);
+    while let Some((number, field)) = message.next_field()? {
+        match (number, field) {
+            (1, Field::Bytes(table)) => {
+                let mut table = Message::new(table);
+                while let Some((number, field)) = table.next_field()? {
+                    if let (1, Field::Bytes(value)) = (number, field) {
+                        block.strings.push(String::from_utf8_lossy(value).into_owned());
+                    }
+                }
+            }
+            (2, Field::Bytes(group)) => groups.push(group),
+            (17, Field::Varint(value)) => block.granularity = value as i64,
+            (19, Field::Varint(value)) => block.lat_offset = value as i64,
+            (20, Field::Varint(value)) => block.lon_offset = value as i64,
+            _ => {}
+        }
+    }
+
+    for group in groups {
+        let mut message = Message::new(group);
+        while let Some((number, field)) = message.next_field()? {
+            match (number, field) {
+                (1, Field::Bytes(node)) => visit(OsmElement::Node(read_node(node, &block)?)),
+                (2, Field::Bytes(dense)) => read_dense_nodes(dense, &block, visit)?,
+                (3, Field::Bytes(way)) => visit(OsmElement::Way(read_way(way, &block)?)),
+                (4, Field::Bytes(relation)) => visit(OsmElement::Relation(read_relation(relation, &block)?)),
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_node(data: &[u8], block: &PrimitiveBlock) -> Result<OsmNode, ExtractError> {
+    let (mut id, mut lat, mut lon) = (0, 0, 0);
+    let (mut keys, mut values) = (Vec::new(), Vec::new());
+    let mut message = Message::new(data);
+    while let Some((number, field)) = message.next_field()? {
+        match (number, field) {
+            (1, Field::Varint(value)) => id = zigzag_decode(value),
+            (2, field) => read_numbers(field, &mut keys)?,
+            (3, field) => read_numbers(field, &mut values)?,
+            (8, Field::Varint(value)) => lat = zigzag_decode(value),
+            (9, Field::Varint(value)) => lon = zigzag_decode(value),
+            _ => {}
+        }
+    }
+
+    Ok(OsmNode {
+        id,
+        lat: block.get_lat(lat),
+        lon: block.get_lon(lon),
+        tags: block.get_tags(&keys, &values)?,
+    })
+}
+
+fn read_dense_nodes(
+    data: &[u8],
+    block: &PrimitiveBlock,
+    visit: &mut dyn FnMut(OsmElement),
+) -> Result<(), ExtractError> {
+    let (mut ids, mut lats, mut lons, mut keys_values) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    let mut message = Message::new(data);
+    while let Some((number, field)) = message.next_field()? {
+        match number {
+            1 => read_numbers(field, &mut ids)?,
+            8 => read_numbers(field, &mut lats)?,
+            9 => read_numbers(field, &mut lons)?,
+            10 => read_numbers(field, &mut keys_values)?,
+            _ => {}
+        }
+    }
+    if ids.len() != lats.len() || ids.len() != lons.len() {
+        return Err(invalid_pbf("dense nodes with a different number of ids and coordinates"));
+    }
+
+    // the tags of all nodes, each list ends with a 0
+    let mut position = 0;
+    let ids = decode_deltas(&ids);
+    let lats = decode_deltas(&lats);
+    let lons = decode_deltas(&lons);
+    for ((id, lat), lon) in ids.into_iter().zip(lats).zip(lons) {
+        let mut tags = Vec::new();
+        while position < keys_values.len() {
+            let key = keys_values[position];
+            position += 1;
+            if key == 0 {
+                break;
+            }
+            let value = *keys_values.get(position).ok_or_else(|| invalid_pbf("dense node tag without a value"))?;
+            position += 1;
+            tags.push((block.get_string(key)?, block.get_string(value)?));
+        }
+
+        visit(OsmElement::Node(OsmNode {
+            id,
+            lat: block.get_lat(lat),
+            lon: block.get_lon(lon),
+            tags,
+        }));
+    }
+    Ok(())
+}
+
+fn read_way(data: &[u8], block: &PrimitiveBlock) -> Result<OsmWay, ExtractError> {
+    let mut id = 0;
+    let (mut keys, mut values, mut refs) = (Vec::new(), Vec::new(), Vec::new());
+    let mut message = Message::new(data);
+    while let Some((number, field)) = message.next_field()? {
+        match (number, field) {
+            (1, Field::Varint(value)) => id = value as i64,
+            (2, field) => read_numbers(field, &mut keys)?,
+            (3, field) => read_numbers(field, &mut values)?,
+            (8, field) => read_numbers(field, &mut refs)?,
+            _ => {}
+        }
+    }
+
+    Ok(OsmWay {
+        id,
+        nodes: decode_deltas(&refs),
+        tags: block.get_tags(&keys, &values)?,
+    })
+}
+
+fn read_relation(data: &[u8], block: &PrimitiveBlock) -> Result<OsmRelation, ExtractError> {
+    let mut id = 0;
+    let (mut keys, mut values) = (Vec::new(), Vec::new());
+    let (mut roles, mut member_ids, mut types) = (Vec::new(), Vec::new(), Vec::new());
+    let mut message = Message::new(data);
+    while let Some((number, field)) = message.next_field()? {
+        match (number, field) {
+            (1, Field::Varint(value)) => id = value as i64,
+            (2, field) => read_numbers(field, &mut keys)?,
+            (3, field) => read_numbers(field, &mut values)?,
+            (8, field) => read_numbers(field, &mut roles)?,
+            (9, field) => read_numbers(field, &mut member_ids)?,
+            (10, field) => read_numbers(field, &mut types)?,
+            _ => {}
+        }
+    }
+    if roles.len() != member_ids.len() || roles.len() != types.len() {
+        return Err(invalid_pbf("relation with a different number of member ids, roles and types"));
+    }
+
+    let mut members = Vec::with_capacity(roles.len());
+    for ((role, id), kind) in roles.iter().zip(decode_deltas(&member_ids)).zip(types) {
+        let kind = match kind {
+            0 => MemberType::Node,
+            1 => MemberType::Way,
+            2 => MemberType::Relation,
+            _ => return Err(invalid_pbf(format!("unknown member type {}", kind))),
+        };
+        members.push(OsmMember {
+            kind,
+            id,
+            role: block.get_string(*role)?,
+        });
+    }
+
+    Ok(OsmRelation {
+        id,
+        members,
+        tags: block.get_tags(&keys, &values)?,
+    })
+}
+
+fn invalid_xml<T: ToString>(reason: T) -> ExtractError {
+    ExtractError::InvalidXml(reason.to_string())
+}
+
+// whether the buffer holds an entire tag, attribute values may contain `>`
+fn is_complete(buffer: &[u8]) -> bool {
+    let tag = match buffer.iter().position(|byte| *byte == b'<') {
+        Some(start) => &buffer[start..],
+        None => return true,
+    };
+    if tag.starts_with(b"<!--") {
+        return tag.ends_with(b"-->");
+    }
+
+    let mut quote = None;
+    for byte in tag {
+        match quote {
+            Some(open) if *byte == open => quote = None,
+            Some(_) => {}
+            None if *byte == b'"' || *byte == b'\'' => quote = Some(*byte),
+            None => {}
+        }
+    }
+    quote.is_none()
+}
+
+fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let decoded = rest.find(';').and_then(|end| {
+            let entity = &rest[1..end];
+            let character = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                _ if entity.starts_with("#x") => u32::from_str_radix(&entity[2..], 16).ok().and_then(std::char::from_u32),
+                _ if entity.starts_with('#') => entity[1..].parse().ok().and_then(std::char::from_u32),
+                _ => None,
+            };
+            character.map(|character| (character, end))
+        });
+
+        match decoded {
+            Some((character, end)) => {
+                result.push(character);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+fn parse_attributes(text: &str) -> Result<BTreeMap<&str, String>, ExtractError> {
+    let mut result = BTreeMap::new();
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        let equals = rest.find('=').ok_or_else(|| invalid_xml(format!("attribute without a value in `{}`", text)))?;
+        let name = rest[..equals].trim();
+        let value = rest[equals + 1..].trim_start();
+        let quote = match value.chars().next() {
+            Some(quote) if quote == '"' || quote == '\'' => quote,
+            _ => return Err(invalid_xml(format!("unquoted attribute `{}`", name))),
+        };
+        let end = value[1..]
+            .find(quote)
+            .ok_or_else(|| invalid_xml(format!("unterminated attribute `{}`", name)))?
+            + 1;
+        result.insert(name, unescape(&value[1..end]));
+        rest = value[end + 1..].trim_start();
+    }
+    Ok(result)
+}
+
+fn get_attribute<T: FromStr>(attributes: &BTreeMap<&str, String>, name: &str, tag: &str) -> Result<T, ExtractError> {
+    attributes
+        .get(name)
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| invalid_xml(format!("<{}> without a valid {}", tag, name)))
+}
+
+// OSM XML only nests tags, nodes and members inside elements, so a tag by tag scan suffices
+fn read_xml<R: BufRead>(mut reader: R, visit: &mut dyn FnMut(OsmElement)) -> Result<(), ExtractError> {
+    let mut buffer = Vec::new();
+    let mut current: Option<OsmElement> = None;
+    loop {
+        buffer.clear();
+        let mut total = 0;
+        loop {
+            let read = reader.read_until(b'>', &mut buffer).map_err(invalid_xml)?;
+            total += read;
+            if read == 0 || is_complete(&buffer) {
+                break;
+            }
+        }
+        if total == 0 {
+            break;
+        }
+
+        let start = match buffer.iter().position(|byte| *byte == b'<') {
+            Some(start) => start,
+            None => continue, // whitespace at the end of the file
+        };
+        let text = std::str::from_utf8(&buffer[start..]).map_err(|_| invalid_xml("invalid UTF-8"))?;
+        if !text.ends_with('>') {
+            return Err(invalid_xml("unexpected end of file"));
+        }
+        if text.starts_with("<?") || text.starts_with("<!") {
+            continue;
+        }
+
+        if text.starts_with("</") {
+            let name = text[2..text.len() - 1].trim();
+            if name == "node" || name == "way" || name == "relation" {
+                if let Some(element) = current.take() {
+                    visit(element);
+                }
+            }
+            continue;
+        }
+
+        let self_closing = text.ends_with("/>");
+        let inner = &text[1..text.len() - if self_closing { 2 } else { 1 }];
+        let (name, attributes) = match inner.find(char::is_whitespace) {
+            Some(index) => (&inner[..index], &inner[index..]),
+            None => (inner, ""),
+        };
+
+        let element = match name {
+            "node" | "way" | "relation" => {
+                let attributes = parse_attributes(attributes)?;
+                let id = get_attribute(&attributes, "id", name)?;
+                match name {
+                    "node" => OsmElement::Node(OsmNode {
+                        id,
+                        lat: get_attribute(&attributes, "lat", name)?,
+                        lon: get_attribute(&attributes, "lon", name)?,
+                        tags: Vec::new(),
+                    }),
+                    "way" => OsmElement::Way(OsmWay {
+                        id,
+                        nodes: Vec::new(),
+                        tags: Vec::new(),
+                    }),
+                    _ => OsmElement::Relation(OsmRelation {
+                        id,
+                        members: Vec::new(),
+                        tags: Vec::new(),
+                    }),
+                }
+            }
+            // tags of changesets and the like don't have a current element
+            "tag" => {
+                if let Some(element) = current.as_mut() {
+                    let attributes = parse_attributes(attributes)?;
+                    let key = get_attribute(&attributes, "k", name)?;
+                    let value = get_attribute(&attributes, "v", name)?;
+                    element.get_tags_mut().push((key, value));
+                }
+                continue;
+            }
+            "nd" => {
+                if let Some(OsmElement::Way(way)) = current.as_mut() {
+                    way.nodes.push(get_attribute(&parse_attributes(attributes)?, "ref", name)?);
+                }
+                continue;
+            }
+            "member" => {
+                if let Some(OsmElement::Relation(relation)) = current.as_mut() {
+                    let attributes = parse_attributes(attributes)?;
+                    let kind = match attributes.get("type").map(String::as_str) {
+                        Some("node") => MemberType::Node,
+                        Some("way") => MemberType::Way,
+                        Some("relation") => MemberType::Relation,
+                        _ => return Err(invalid_xml("<member> without a valid type")),
+                    };
+                    relation.members.push(OsmMember {
+                        kind,
+                        id: get_attribute(&attributes, "ref", name)?,
+                        role: attributes.get("role").cloned().unwrap_or_default(),
+                    });
+                }
+                continue;
+            }
+            _ => continue,
+        };
+
+        if self_closing {
+            visit(element);
+        } else {
+            current = Some(element);
+        }
+    }
+
+    if current.is_some() {
+        return Err(invalid_xml("unexpected end of file"));
+    }
+    Ok(())
+}
@@ -1,19 +1,29 @@
 use crate::entities::tile_coord::TileCoordinate;
+use std::env;
 use std::fs;
+use std::path::PathBuf;
 
+pub mod area;
+pub mod compact_tile;
+pub mod extract;
+pub mod csr_graph;
 pub mod profile;
+pub mod profile_validation;
 pub mod tiles;
 
-pub fn get_car_profile_path() -> &'static str {
-    "./car.jsonld"
-}
+pub fn get_default_profile_dirs() -> Vec<PathBuf> {
+    let mut result = vec![PathBuf::from(".")];
 
-pub fn get_pedestrian_profile_path() -> &'static str {
-    "./pedestrian.jsonld"
-}
+    // profiles shipped next to the executable
+    if let Ok(exe) = env::current_exe() {
+        if let Some(dir) = exe.parent() {
+            result.push(dir.to_path_buf());
+        }
+    }
 
-pub fn get_bicycle_profile_path() -> &'static str {
-    "./bicycle.jsonld"
+    // the bundled profiles of the source tree this was built from
+    result.push(PathBuf::from(env!("CARGO_MANIFEST_DIR")));
+    result
 }
 
 pub fn get_tile_path(root: &str, tile: &TileCoordinate) -> String {
@@ -33,3 +43,12 @@ pub fn get_binary_tile_path(root: &str, tile: &TileCoordinate) -> String {
 
     format!("{}/{}.bin", dir, tile.y)
 }
+
+pub fn get_geojson_tile_path(root: &str, tile: &TileCoordinate) -> String {
+    let dir = format!("{}/{}/{}", root, tile.zoom, tile.x);
+    if fs::metadata(&dir).is_err() {
+        fs::create_dir_all(&dir).ok();
+    }
+
+    format!("{}/{}.geojson", dir, tile.y)
+}
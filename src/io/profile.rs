@@ -1,35 +1,104 @@
-use crate::entities::profile::Profile;
-use std::fs::File;
-use std::io::Read;
-use crate::io::{get_car_profile_path, get_pedestrian_profile_path, get_bicycle_profile_path};
+use crate::entities::profile::{Profile, ProfileSource};
+use crate::util::hash::fnv1a;
+use crate::io::get_default_profile_dirs;
+use crate::io::profile_validation::validate_profile;
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
 
-#[derive(Debug)]
-pub enum Error {
-    NotAFile,
+#[derive(Error, Debug, Clone)]
+pub enum ProfileError {
+    #[error("Invalid file path `{0}`")]
+    NotAFile(String),
+    #[error("Invalid profile `{path}` at line {line}, column {column}: {reason}")]
+    InvalidProfile {
+        path: String,
+        line: usize,
+        column: usize,
+        reason: String,
+    },
+    #[error("Profile `{0}` not found, searched in: {1}")]
+    NotFound(String, String),
+    #[error("Invalid profile `{0}`:\n{1}")]
+    InvalidRules(String, String),
 }
 
-pub fn load_car_profile() -> Result<Profile, Error> {
-    load_profile(get_car_profile_path())
+// the bundled profiles, the tests use these
+#[cfg(test)]
+pub fn load_car_profile() -> Result<Profile, ProfileError> {
+    load_named_profile("car", &[])
 }
 
-pub fn load_pedestrian_profile() -> Result<Profile, Error> {
-    load_profile(get_pedestrian_profile_path())
+#[cfg(test)]
+pub fn load_pedestrian_profile() -> Result<Profile, ProfileError> {
+    load_named_profile("pedestrian", &[])
 }
 
-pub fn load_bicycle_profile() -> Result<Profile, Error> {
-    load_profile(get_bicycle_profile_path())
+#[cfg(test)]
+pub fn load_bicycle_profile() -> Result<Profile, ProfileError> {
+    load_named_profile("bicycle", &[])
 }
 
-fn load_profile(path: &str) -> Result<Profile, Error> {
-    let mut file = match File::open(path) {
-        Ok(file) => file,
-        _ => return Err(Error::NotAFile),
-    };
+// looks for `<name>.jsonld` or `<name>.json`,
+// first in the given directories and then in the default ones
+pub fn find_profile_path(name: &str, search_dirs: &[&str]) -> Result<PathBuf, ProfileError> {
+    let mut dirs: Vec<PathBuf> = search_dirs.iter().map(PathBuf::from).collect();
+    dirs.extend(get_default_profile_dirs());
+
+    for dir in dirs.iter() {
+        for extension in &["jsonld", "json"] {
+            let candidate = dir.join(format!("{}.{}", name, extension));
+            if candidate.is_file() {
+                return Ok(candidate);
+            }
+        }
+    }
+
+    let searched: Vec<String> = dirs.iter().map(|d| d.display().to_string()).collect();
+    Err(ProfileError::NotFound(name.to_owned(), searched.join(", ")))
+}
+
+pub fn load_named_profile(name: &str, search_dirs: &[&str]) -> Result<Profile, ProfileError> {
+    let path = find_profile_path(name, search_dirs)?;
+    load_profile(&path)
+}
 
-    let mut data = String::new();
-    if file.read_to_string(&mut data).is_err() {
-        return Err(Error::NotAFile);
+pub fn load_profile<P: AsRef<Path>>(path: P) -> Result<Profile, ProfileError> {
+    let path = path.as_ref();
+    let data = match fs::read_to_string(path) {
+        Ok(data) => data,
+        _ => return Err(ProfileError::NotAFile(path.display().to_string())),
     };
 
-    Ok(serde_json::from_str(&data).unwrap())
-}
\ No newline at end of file
+    let mut profile = parse_profile(&data).map_err(|e| ProfileError::InvalidProfile {
+        path: path.display().to_string(),
+        line: e.line(),
+        column: e.column(),
+        reason: e.to_string(),
+    })?;
+
+    // catches what would otherwise panic during evaluation, e.g. rules without the right conclusion
+    let errors: Vec<String> = validate_profile(&data)
+        .iter()
+        .filter(|issue| issue.is_error())
+        .map(|issue| issue.to_string())
+        .collect();
+    if !errors.is_empty() {
+        return Err(ProfileError::InvalidRules(path.display().to_string(), errors.join("\n")));
+    }
+
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    profile.set_source(ProfileSource {
+        name,
+        hash: fnv1a(data.as_bytes()),
+    });
+
+    Ok(profile)
+}
+
+pub fn parse_profile(data: &str) -> Result<Profile, serde_json::Error> {
+    serde_json::from_str(data)
+}
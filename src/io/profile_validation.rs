@@ -0,0 +1,394 @@
+use crate::io::tiles::IRI_VALUED_TERMS;
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProfileIssue {
+    pub severity: Severity,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl ProfileIssue {
+    fn error(line: Option<usize>, message: String) -> ProfileIssue {
+        ProfileIssue { severity: Severity::Error, line, message }
+    }
+
+    fn warning(line: Option<usize>, message: String) -> ProfileIssue {
+        ProfileIssue { severity: Severity::Warning, line, message }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for ProfileIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match self.line {
+            Some(line) => write!(f, "{} (line {}): {}", severity, line, self.message),
+            None => write!(f, "{}: {}", severity, self.message),
+        }
+    }
+}
+
+// each rule list, with the conclusion its rules have to contain
+const RULE_LISTS: [(&str, &str); 6] = [
+    ("hasAccessRules", "hasAccess"),
+    ("hasOnewayRules", "isOneway"),
+    ("hasSpeedRules", "hasSpeed"),
+    ("hasPriorityRules", "hasPriority"),
+    ("hasObstacleRules", "isObstacle"),
+    ("hasObstacleTimeRules", "hasObstacleTime"),
+];
+
+const COMPARISONS: [&str; 4] = ["lessThan", "lessThanOrEqual", "greaterThan", "greaterThanOrEqual"];
+
+pub fn validate_profile(data: &str) -> Vec<ProfileIssue> {
+    let mut issues = Vec::new();
+
+    let v: Value = match serde_json::from_str(data) {
+        Ok(v) => v,
+        Err(e) => {
+            issues.push(ProfileIssue::error(Some(e.line()), e.to_string()));
+            return issues;
+        }
+    };
+
+    let profile = match v.as_object() {
+        Some(profile) => profile,
+        None => {
+            issues.push(ProfileIssue::error(Some(1), "a profile should be a JSON object".to_owned()));
+            return issues;
+        }
+    };
+
+    if let Some(speed) = profile.get("hasMaxSpeed") {
+        if !speed.is_number() {
+            issues.push(ProfileIssue::error(None, "hasMaxSpeed should be a number".to_owned()));
+        }
+    }
+
+    if let Some(mode) = profile.get("hasTransportMode") {
+        if !mode.is_string() {
+            issues.push(ProfileIssue::error(None, "hasTransportMode should be a string".to_owned()));
+        }
+    }
+
+    let prefixes = get_prefixes(profile);
+    for (list, conclusion) in RULE_LISTS.iter() {
+        match profile.get(*list) {
+            Some(Value::Array(rules)) => {
+                let lines = find_element_lines(data, list);
+                validate_rules(list, conclusion, rules, &lines, &prefixes, &mut issues);
+            }
+            Some(_) => issues.push(ProfileIssue::error(None, format!("{} should be a list", list))),
+            None => issues.push(ProfileIssue::error(None, format!("missing {}", list))),
+        }
+    }
+
+    issues
+}
+
+fn get_prefixes(profile: &Map<String, Value>) -> HashSet<String> {
+    let mut result = HashSet::new();
+    if let Some(Value::Object(context)) = profile.get("@context") {
+        for (key, value) in context {
+            if value.is_string() && !key.contains(':') {
+                result.insert(key.to_owned());
+            }
+        }
+    }
+    result
+}
+
+fn validate_rules(
+    list: &str,
+    conclusion: &str,
+    rules: &[Value],
+    lines: &[usize],
+    prefixes: &HashSet<String>,
+    issues: &mut Vec<ProfileIssue>,
+) {
+    for (index, rule) in rules.iter().enumerate() {
+        let line = lines.get(index).copied();
+        let rule = match rule.as_object() {
+            Some(rule) => rule,
+            None => {
+                issues.push(ProfileIssue::error(line, format!("{}: a rule should be an object", list)));
+                continue;
+            }
+        };
+
+        match rule.get("concludes").and_then(|c| c.as_object()) {
+            Some(concludes) => validate_conclusion(list, conclusion, concludes, line, issues),
+            None => issues.push(ProfileIssue::error(line, format!("{}: rule without conclusion", list))),
+        }
+
+        if let Some(order) = rule.get("hasOrder") {
+            if !order.is_number() {
+                issues.push(ProfileIssue::error(line, format!("{}: hasOrder should be a number", list)));
+            }
+        }
+
+        if let Some(condition) = rule.get("match") {
+            validate_condition(list, condition, line, prefixes, issues);
+        }
+
+        for key in rule.keys() {
+            if !["concludes", "hasOrder", "match"].contains(&key.as_str()) {
+                issues.push(ProfileIssue::warning(line, format!("{}: unknown rule property {}", list, key)));
+            }
+        }
+    }
+
+    find_shadowed_rules(list, rules, lines, issues);
+}
+
+fn validate_conclusion(
+    list: &str,
+    expected: &str,
+    concludes: &Map<String, Value>,
+    line: Option<usize>,
+    issues: &mut Vec<ProfileIssue>,
+) {
+    match concludes.get(expected) {
+        Some(Value::Bool(_)) if expected.starts_with("is") || expected == "hasAccess" => {}
+        Some(Value::Number(_)) if !(expected.starts_with("is") || expected == "hasAccess") => {}
+        Some(_) => issues.push(ProfileIssue::error(line, format!("{}: {} has the wrong type", list, expected))),
+        None => issues.push(ProfileIssue::error(line, format!("{}: conclusion lacks {}", list, expected))),
+    }
+
+    if let Some(Value::Number(priority)) = concludes.get("hasPriority") {
+        if priority.as_f64() == Some(0.) {
+            issues.push(ProfileIssue::error(line, format!("{}: hasPriority can't be 0", list)));
+        }
+    }
+
+    for key in concludes.keys() {
+        let allowed = key == expected || (expected == "isOneway" && key == "isReversed");
+        if !allowed {
+            issues.push(ProfileIssue::warning(line, format!("{}: {} is ignored in this list", list, key)));
+        }
+    }
+}
+
+fn validate_condition(
+    list: &str,
+    condition: &Value,
+    line: Option<usize>,
+    prefixes: &HashSet<String>,
+    issues: &mut Vec<ProfileIssue>,
+) {
+    let condition = match condition.as_object() {
+        Some(condition) => condition,
+        None => {
+            issues.push(ProfileIssue::error(line, format!("{}: a condition should be an object", list)));
+            return;
+        }
+    };
+
+    for (key, value) in condition {
+        match key.as_str() {
+            "hasPredicate" | "hasObject" => match value.as_str() {
+                Some(concept) => check_concept(list, concept, prefixes, line, issues),
+                None => issues.push(ProfileIssue::error(line, format!("{}: {} should be a string", list, key))),
+            },
+            "and" | "or" => match value.as_array() {
+                Some(nested) => {
+                    for condition in nested {
+                        validate_condition(list, condition, line, prefixes, issues);
+                    }
+                }
+                None => issues.push(ProfileIssue::error(line, format!("{}: {} should be a list", list, key))),
+            },
+            "not" => validate_condition(list, value, line, prefixes, issues),
+            comparison if COMPARISONS.contains(&comparison) => {
+                if !value.is_number() {
+                    issues.push(ProfileIssue::error(line, format!("{}: {} should be a number", list, key)));
+                }
+                if !condition.contains_key("hasPredicate") {
+                    let message = format!("{}: {} without hasPredicate never matches", list, key);
+                    issues.push(ProfileIssue::warning(line, message));
+                }
+            }
+            _ => issues.push(ProfileIssue::warning(line, format!("{}: unknown condition property {}", list, key))),
+        }
+    }
+
+    // objects are IRIs, these only match tags that are declared as such in the tiles
+    if let (Some(Value::String(predicate)), Some(_)) = (condition.get("hasPredicate"), condition.get("hasObject")) {
+        if predicate.starts_with("osm:") && !IRI_VALUED_TERMS.contains(&predicate.as_str()) {
+            let message = format!(
+                "{}: {} is not an IRI in the tile context, its values won't match hasObject",
+                list, predicate
+            );
+            issues.push(ProfileIssue::warning(line, message));
+        }
+    }
+}
+
+fn check_concept(
+    list: &str,
+    concept: &str,
+    prefixes: &HashSet<String>,
+    line: Option<usize>,
+    issues: &mut Vec<ProfileIssue>,
+) {
+    match concept.find(':') {
+        Some(position) => {
+            let prefix = &concept[..position];
+            let is_absolute = concept[position..].starts_with("://");
+            if !is_absolute && !prefixes.contains(prefix) {
+                let message = format!("{}: prefix {} of {} is not defined in the @context", list, prefix, concept);
+                issues.push(ProfileIssue::warning(line, message));
+            }
+        }
+        None => {
+            let message = format!("{}: {} is not a known concept", list, concept);
+            issues.push(ProfileIssue::warning(line, message));
+        }
+    }
+}
+
+// Same ordering as the evaluator: ascending `hasOrder`, then file order, unordered rules last.
+// A rule is unreachable if an earlier rule matches everything it matches.
+fn find_shadowed_rules(list: &str, rules: &[Value], lines: &[usize], issues: &mut Vec<ProfileIssue>) {
+    let mut sorted: Vec<usize> = (0..rules.len()).collect();
+    sorted.sort_by(|a, b| {
+        let a = rules[*a].get("hasOrder").and_then(|o| o.as_f64());
+        let b = rules[*b].get("hasOrder").and_then(|o| o.as_f64());
+        match (a, b) {
+            (Some(x), Some(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    });
+
+    let mut catch_all: Option<usize> = None;
+    let mut present: HashSet<String> = HashSet::new();
+    let mut exact: HashSet<(String, String)> = HashSet::new();
+
+    for index in sorted {
+        let line = lines.get(index).copied();
+        if let Some(previous) = catch_all {
+            let message = match lines.get(previous) {
+                Some(previous) => format!("{}: unreachable, the rule on line {} matches everything", list, previous),
+                None => format!("{}: unreachable, an earlier rule matches everything", list),
+            };
+            issues.push(ProfileIssue::warning(line, message));
+            continue;
+        }
+
+        let condition = match rules[index].get("match") {
+            None => {
+                catch_all = Some(index);
+                continue;
+            }
+            Some(Value::Object(condition)) if condition.is_empty() => {
+                catch_all = Some(index);
+                continue;
+            }
+            Some(Value::Object(condition)) => condition,
+            Some(_) => continue,
+        };
+
+        // only plain tag conditions are checked, compound ones are too hard to compare
+        if condition.keys().any(|k| k != "hasPredicate" && k != "hasObject") {
+            continue;
+        }
+
+        let predicate = condition.get("hasPredicate").and_then(|p| p.as_str());
+        let object = condition.get("hasObject").and_then(|o| o.as_str());
+        match (predicate, object) {
+            (Some(predicate), Some(object)) => {
+                let tag = (predicate.to_owned(), object.to_owned());
+                if present.contains(predicate) || exact.contains(&tag) {
+                    let message = format!("{}: unreachable, {} = {} is matched by an earlier rule", list, predicate, object);
+                    issues.push(ProfileIssue::warning(line, message));
+                }
+                exact.insert(tag);
+            }
+            (Some(predicate), None) => {
+                if present.contains(predicate) {
+                    let message = format!("{}: unreachable, {} is matched by an earlier rule", list, predicate);
+                    issues.push(ProfileIssue::warning(line, message));
+                }
+                present.insert(predicate.to_owned());
+            }
+            _ => {}
+        }
+    }
+}
+
+// Finds the line on which every element of the top-level list `key` starts,
+// serde_json doesn't keep track of positions itself.
+fn find_element_lines(data: &str, key: &str) -> Vec<usize> {
+    let bytes = data.as_bytes();
+    let mut result = Vec::new();
+
+    let mut line = 1;
+    let mut depth = 0;
+    let mut last_key: &str = "";
+    let mut key_matches = false;
+    let mut in_list = false;
+    let mut expecting_element = false;
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        if in_list && depth == 2 && expecting_element && !c.is_ascii_whitespace() && c != b']' {
+            result.push(line);
+            expecting_element = false;
+        }
+
+        match c {
+            b'\n' => line += 1,
+            b'"' => {
+                let start = i + 1;
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    if bytes[i] == b'\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                if depth == 1 {
+                    last_key = &data[start..i.min(bytes.len())];
+                }
+            }
+            b':' if depth == 1 => key_matches = last_key == key,
+            b'{' | b'[' => {
+                depth += 1;
+                if c == b'[' && depth == 2 && key_matches {
+                    in_list = true;
+                    expecting_element = true;
+                }
+            }
+            b'}' | b']' => {
+                if in_list && depth == 2 {
+                    return result;
+                }
+                depth -= 1;
+            }
+            b',' if in_list && depth == 2 => expecting_element = true,
+            _ => {}
+        }
+        i += 1;
+    }
+
+    result
+}
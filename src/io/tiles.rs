@@ -1,18 +1,26 @@
-use crate::entities::weighted_tile::WeightedTile;
+use crate::entities::weighted_tile::{WeightedTile, MAGIC, SCHEMA_VERSION};
 use crate::io::get_tile_path;
+use crate::io::compact_tile::{decode_compact_tile, is_compact_tile};
+use crate::io::csr_graph::{is_csr_graph, CsrError, CsrGraph};
+use crate::util::maxspeed::{MaxSpeed, SpeedTable};
 use serde_json::{json, Value};
 use std::fs::File;
-use std::io::{Write, Read};
+use std::fmt;
+use std::io::{BufReader, Read, Write};
 
 use crate::entities::node::Node;
+use crate::entities::profile::{Profile, ProfileSource};
+use crate::entities::property::Property;
+use crate::entities::relation::{Member, Relation};
 use crate::entities::tile::Tile;
 use crate::entities::way::Way;
 use crate::entities::tile_coord::TileCoordinate;
-use std::{collections::BTreeMap, fs};
+use std::{collections::{BTreeMap, BTreeSet}, fs};
 
 use cached::SizedCache;
 use flexbuffers::FlexbufferSerializer;
-use serde::Serialize;
+use serde::de::{DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
 
 use thiserror::Error;
 use anyhow::Result;
@@ -20,6 +28,42 @@ use anyhow::Result;
 use flate2::Compression;
 use flate2::{read::GzDecoder, write::GzEncoder};
 
+// tags whose values are known to be IRIs (e.g. `osm:Residential`) rather than literals,
+// declared in the context of a derived tile when its profile uses them
+pub const IRI_VALUED_TERMS: &[&str] = &[
+    "osm:access",
+    "osm:barrier",
+    "osm:bicycle",
+    "osm:construction",
+    "osm:crossing",
+    "osm:cycleway",
+    "osm:footway",
+    "osm:highway",
+    "osm:motor_vehicle",
+    "osm:motorcar",
+    "osm:oneway_bicycle",
+    "osm:oneway",
+    "osm:smoothness",
+    "osm:surface",
+    "osm:tracktype",
+    "osm:vehicle",
+];
+
+// prefixes of the tile context, tag values that use one of these are compact IRIs
+const CONTEXT_PREFIXES: &[(&str, &str)] = &[
+    ("tiles", "https://w3id.org/tree/terms#"),
+    ("hydra", "http://www.w3.org/ns/hydra/core#"),
+    ("osm", "https://w3id.org/openstreetmap/terms#"),
+    ("rdfs", "http://www.w3.org/2000/01/rdf-schema#"),
+    ("geo", "http://www.w3.org/2003/01/geo/wgs84_pos#"),
+    ("dcterms", "http://purl.org/dc/terms/"),
+    ("prov", "http://www.w3.org/ns/prov#"),
+    ("opp", "https://w3id.org/openplannerteam/profile#"),
+];
+
+// the speed limit of a way in km/h, as interpreted from its `osm:maxspeed` tag
+const INTERPRETED_MAXSPEED: &str = "opp:hasMaxSpeed";
+
 #[derive(Error, Debug, Clone)]
 pub enum TileError {
     #[error("Invalid file path `{0}`")]
@@ -28,17 +72,39 @@ pub enum TileError {
     InvalidFile(String),
     #[error("Invalid JSON")]
     NotJson,
-    #[error("Invalid JSON-LD")]
-    MissingID,
-    #[error("Invalid Tile")]
-    MissingLatitude,
-    #[error("Invalid Tile")]
-    MissingLongitude,
-    #[error("Invalid Tile")]
-    MissingNodes,
+    #[error("Entity #{0} of the graph has no @id")]
+    MissingID(usize),
+    #[error("Entity `{0}` has no @type")]
+    MissingType(String),
+    #[error("Node `{0}` has no latitude")]
+    MissingLatitude(String),
+    #[error("Node `{0}` has no longitude")]
+    MissingLongitude(String),
+    #[error("Way `{0}` has no nodes")]
+    MissingNodes(String),
+    #[error("Relation `{0}` has no members")]
+    MissingMembers(String),
+    #[error("Invalid entity `{id}`: {reason}")]
+    InvalidEntity { id: String, reason: String },
+    #[error("Invalid binary tile `{0}`")]
+    InvalidBinary(String),
+    #[error("Incompatible binary tile `{0}`: {1}")]
+    IncompatibleBinary(String, String),
+}
+
+// what to do with entities that can't be parsed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParseMode {
+    Strict,  // fail the entire tile
+    Lenient, // skip the entity, or just the offending value, and report a warning
 }
 
-pub fn parse_tile(coordinate: &TileCoordinate, data: String, ) -> Result<Tile, TileError> {
+pub fn parse_tile(
+    coordinate: &TileCoordinate,
+    data: String,
+    mode: ParseMode,
+    speeds: &SpeedTable,
+) -> Result<(Tile, Vec<TileError>), TileError> {
     let v: Value = match serde_json::from_str(&data) {
         Ok(v) => v,
         Err(_) => return Err(TileError::NotJson),
@@ -49,44 +115,218 @@ pub fn parse_tile(coordinate: &TileCoordinate, data: String, ) -> Result<Tile, T
         None => return Err(TileError::NotJson),
     };
 
-    let mut nodes = BTreeMap::new();
-    let mut ways = BTreeMap::new();
+    let mut entities = (BTreeMap::new(), BTreeMap::new(), BTreeMap::new());
+    let mut warnings = Vec::new();
+    for (index, entity) in graph.iter().enumerate() {
+        add_entity(entity, index, mode, speeds, &mut entities, &mut warnings)?;
+    }
+    check_way_nodes(&mut entities, mode, &mut warnings)?;
 
-    for entity in graph {
-        if entity["@type"].as_str().unwrap() == "osm:Node" {
-            match create_node(entity) {
-                Ok(node) => nodes.insert(node.get_id().to_string(), node),
-                _ => None,
-            };
+    let (nodes, ways, relations) = entities;
+    Ok((Tile::new(*coordinate, nodes, ways, relations), warnings))
+}
+
+type Entities = (BTreeMap<String, Node>, BTreeMap<String, Way>, BTreeMap<String, Relation>);
+
+// the tasks rely on every node of a way being part of the tile,
+// so ways that refer to a node that's missing or was skipped are dropped as well
+fn check_way_nodes(entities: &mut Entities, mode: ParseMode, warnings: &mut Vec<TileError>) -> Result<(), TileError> {
+    let (nodes, ways, _) = entities;
+    let incomplete: Vec<(String, String)> = ways
+        .iter()
+        .filter_map(|(id, way)| {
+            let missing = way.get_nodes().iter().find(|node| !nodes.contains_key(*node))?;
+            Some((id.clone(), missing.clone()))
+        })
+        .collect();
+
+    for (id, missing) in incomplete {
+        let error = TileError::InvalidEntity { id: id.clone(), reason: format!("missing node `{}`", missing) };
+        match mode {
+            ParseMode::Strict => return Err(error),
+            ParseMode::Lenient => {
+                ways.remove(&id);
+                warnings.push(error);
+            }
         }
-        else if entity["@type"].as_str().unwrap() == "osm:Way" {
-            match create_way(entity) {
-                Ok(way) => ways.insert(way.get_id().to_string(), way),
-                _ => None,
-            };
+    }
+    Ok(())
+}
+
+fn get_entity_name(entity: &Value, index: usize) -> String {
+    match entity["@id"].as_str() {
+        Some(id) => id.to_owned(),
+        None => format!("#{}", index),
+    }
+}
+
+// adds a single entity of the graph, other types than nodes, ways and relations are ignored
+fn add_entity(
+    entity: &Value,
+    index: usize,
+    mode: ParseMode,
+    speeds: &SpeedTable,
+    entities: &mut Entities,
+    warnings: &mut Vec<TileError>,
+) -> Result<(), TileError> {
+    // problems that don't invalidate the entity itself
+    let mut issues = Vec::new();
+    let result = match entity["@type"].as_str() {
+        Some("osm:Node") => create_node(entity, index, &mut issues).map(|node| {
+            entities.0.insert(node.get_id().to_string(), node);
+        }),
+        Some("osm:Way") => create_way(entity, index, speeds, &mut issues).map(|way| {
+            entities.1.insert(way.get_id().to_string(), way);
+        }),
+        Some("osm:Relation") => create_relation(entity, index, &mut issues).map(|relation| {
+            entities.2.insert(relation.get_id().to_string(), relation);
+        }),
+        Some(_) => Ok(()),
+        None => Err(TileError::MissingType(get_entity_name(entity, index))),
+    };
+
+    match mode {
+        ParseMode::Strict => {
+            result?;
+            match issues.into_iter().next() {
+                Some(issue) => Err(issue),
+                None => Ok(()),
+            }
+        }
+        ParseMode::Lenient => {
+            if let Err(e) = result {
+                warnings.push(e);
+            }
+            warnings.extend(issues);
+            Ok(())
         }
     }
+}
+
+type GraphResult = Result<(Entities, Vec<TileError>), TileError>;
+
+// Only looks at `@graph`, and converts its entities one at a time,
+// so that neither the raw text nor a full `Value` tree has to be kept in memory.
+struct TileVisitor<'a> {
+    mode: ParseMode,
+    speeds: &'a SpeedTable,
+}
+
+impl<'de, 'a> Visitor<'de> for TileVisitor<'a> {
+    type Value = Option<GraphResult>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON-LD document")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Option<GraphResult>, A::Error> {
+        let mut graph = None;
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "@graph" {
+                graph = Some(map.next_value_seed(GraphSeed { mode: self.mode, speeds: self.speeds })?);
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(graph)
+    }
+}
+
+struct GraphSeed<'a> {
+    mode: ParseMode,
+    speeds: &'a SpeedTable,
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for GraphSeed<'a> {
+    type Value = GraphResult;
 
-    Ok(Tile::new(*coordinate, nodes, ways))
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<GraphResult, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
 }
 
-pub fn load_tile(coordinate: &TileCoordinate, root_dir: &str) -> Result<Tile, TileError> {
+impl<'de, 'a> Visitor<'de> for GraphSeed<'a> {
+    type Value = GraphResult;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a list of JSON-LD entities")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<GraphResult, A::Error> {
+        let mut entities = (BTreeMap::new(), BTreeMap::new(), BTreeMap::new());
+        let mut warnings = Vec::new();
+
+        let mut index = 0;
+        while let Some(entity) = seq.next_element::<Value>()? {
+            if let Err(e) = add_entity(&entity, index, self.mode, self.speeds, &mut entities, &mut warnings) {
+                // the rest of the document still has to be valid JSON
+                while seq.next_element::<IgnoredAny>()?.is_some() {}
+                return Ok(Err(e));
+            }
+            index += 1;
+        }
+
+        Ok(Ok((entities, warnings)))
+    }
+}
+
+// streaming counterpart of `parse_tile`, also returns the warnings of lenient mode
+pub fn read_tile<R: Read>(
+    coordinate: &TileCoordinate,
+    reader: R,
+    mode: ParseMode,
+    speeds: &SpeedTable,
+) -> Result<(Tile, Vec<TileError>), TileError> {
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let graph = match deserializer.deserialize_map(TileVisitor { mode, speeds }).and_then(|graph| {
+        deserializer.end()?;
+        Ok(graph)
+    }) {
+        Ok(graph) => graph,
+        Err(e) if e.is_io() => {
+            let tile = format!("{}/{}/{}", coordinate.zoom, coordinate.x, coordinate.y);
+            return Err(TileError::InvalidFile(tile));
+        }
+        Err(_) => return Err(TileError::NotJson),
+    };
+
+    match graph {
+        Some(result) => {
+            let (mut entities, mut warnings) = result?;
+            check_way_nodes(&mut entities, mode, &mut warnings)?;
+            let (nodes, ways, relations) = entities;
+            Ok((Tile::new(*coordinate, nodes, ways, relations), warnings))
+        }
+        None => Err(TileError::NotJson),
+    }
+}
+
+// the entities that had to be skipped are returned along with the tile, for the tasks to report
+pub fn load_tile(coordinate: &TileCoordinate, root_dir: &str) -> Result<(Tile, Vec<TileError>), TileError> {
+    let speeds = SpeedTable::default();
+    load_tile_with(coordinate, root_dir, ParseMode::Lenient, &speeds)
+}
+
+pub fn load_tile_with(
+    coordinate: &TileCoordinate,
+    root_dir: &str,
+    mode: ParseMode,
+    speeds: &SpeedTable,
+) -> Result<(Tile, Vec<TileError>), TileError> {
     let path = get_tile_path(root_dir, &coordinate);
     let file = match File::open(&path) {
         Ok(file) => file,
         _ => return Err(TileError::NotAFile(path)),
     };
 
-    let mut decoder = GzDecoder::new(&file);
-    let mut data = String::new();
-    if decoder.read_to_string(&mut data).is_err() {
-        return Err(TileError::InvalidFile(path));
-    }
-
-    parse_tile(coordinate, data)
+    let reader = BufReader::new(GzDecoder::new(file));
+    read_tile(coordinate, reader, mode, speeds).map_err(|e| match e {
+        TileError::InvalidFile(_) => TileError::InvalidFile(path),
+        e => e,
+    })
 }
 
-pub fn load_cached_tile(coordinate: &TileCoordinate, root_dir: &str) -> Result<Tile, TileError> {
+pub fn load_cached_tile(coordinate: &TileCoordinate, root_dir: &str) -> Result<(Tile, Vec<TileError>), TileError> {
     match coordinate.zoom {
         14 => {
             load_cached_tile_l14(coordinate, root_dir)
@@ -110,141 +350,366 @@ pub fn load_cached_tile(coordinate: &TileCoordinate, root_dir: &str) -> Result<T
 }
 
 cached_key!{
-    L14: SizedCache<String, Result<Tile, TileError>> = SizedCache::with_size(400);
+    L14: SizedCache<String, Result<(Tile, Vec<TileError>), TileError>> = SizedCache::with_size(400);
     Key = { format!("{}/{}/{}/{}", root_dir, coordinate.zoom, coordinate.x, coordinate.y) };
 
-    fn load_cached_tile_l14(coordinate: &TileCoordinate, root_dir: &str) -> Result<Tile, TileError> = {
+    fn load_cached_tile_l14(coordinate: &TileCoordinate, root_dir: &str) -> Result<(Tile, Vec<TileError>), TileError> = {
         load_tile(coordinate, root_dir)
     }
 }
 
 cached_key!{
-    L13: SizedCache<String, Result<Tile, TileError>> = SizedCache::with_size(200);
+    L13: SizedCache<String, Result<(Tile, Vec<TileError>), TileError>> = SizedCache::with_size(200);
     Key = { format!("{}/{}/{}/{}", root_dir, coordinate.zoom, coordinate.x, coordinate.y) };
 
-    fn load_cached_tile_l13(coordinate: &TileCoordinate, root_dir: &str) -> Result<Tile, TileError> = {
+    fn load_cached_tile_l13(coordinate: &TileCoordinate, root_dir: &str) -> Result<(Tile, Vec<TileError>), TileError> = {
         load_tile(coordinate, root_dir)
     }
 }
 
 cached_key!{
-    L12: SizedCache<String, Result<Tile, TileError>> = SizedCache::with_size(100);
+    L12: SizedCache<String, Result<(Tile, Vec<TileError>), TileError>> = SizedCache::with_size(100);
     Key = { format!("{}/{}/{}/{}", root_dir, coordinate.zoom, coordinate.x, coordinate.y) };
 
-    fn load_cached_tile_l12(coordinate: &TileCoordinate, root_dir: &str) -> Result<Tile, TileError> = {
+    fn load_cached_tile_l12(coordinate: &TileCoordinate, root_dir: &str) -> Result<(Tile, Vec<TileError>), TileError> = {
         load_tile(coordinate, root_dir)
     }
 }
 
 cached_key!{
-    L11: SizedCache<String, Result<Tile, TileError>> = SizedCache::with_size(50);
+    L11: SizedCache<String, Result<(Tile, Vec<TileError>), TileError>> = SizedCache::with_size(50);
     Key = { format!("{}/{}/{}/{}", root_dir, coordinate.zoom, coordinate.x, coordinate.y) };
 
-    fn load_cached_tile_l11(coordinate: &TileCoordinate, root_dir: &str) -> Result<Tile, TileError> = {
+    fn load_cached_tile_l11(coordinate: &TileCoordinate, root_dir: &str) -> Result<(Tile, Vec<TileError>), TileError> = {
         load_tile(coordinate, root_dir)
     }
 }
 
 cached_key!{
-    L10: SizedCache<String, Result<Tile, TileError>> = SizedCache::with_size(25);
+    L10: SizedCache<String, Result<(Tile, Vec<TileError>), TileError>> = SizedCache::with_size(25);
     Key = { format!("{}/{}/{}/{}", root_dir, coordinate.zoom, coordinate.x, coordinate.y) };
 
-    fn load_cached_tile_l10(coordinate: &TileCoordinate, root_dir: &str) -> Result<Tile, TileError> = {
+    fn load_cached_tile_l10(coordinate: &TileCoordinate, root_dir: &str) -> Result<(Tile, Vec<TileError>), TileError> = {
         load_tile(coordinate, root_dir)
     }
 }
 
 cached_key!{
-    LX: SizedCache<String, Result<Tile, TileError>> = SizedCache::with_size(20);
+    LX: SizedCache<String, Result<(Tile, Vec<TileError>), TileError>> = SizedCache::with_size(20);
     Key = { format!("{}/{}/{}/{}", root_dir, coordinate.zoom, coordinate.x, coordinate.y) };
 
-    fn load_cached_tile_lx(coordinate: &TileCoordinate, root_dir: &str) -> Result<Tile, TileError> = {
+    fn load_cached_tile_lx(coordinate: &TileCoordinate, root_dir: &str) -> Result<(Tile, Vec<TileError>), TileError> = {
         load_tile(coordinate, root_dir)
     }
 }
 
-fn create_node(entity: &Value) -> Result<Node, TileError> {
+// a list of strings, anything else is reported and skipped
+fn get_strings(entity: &Value, key: &str, id: &str, issues: &mut Vec<TileError>) -> Vec<String> {
+    let values = match entity.get(key) {
+        Some(values) => values,
+        None => return Vec::new(),
+    };
+
+    let reason = format!("{} should be a list of strings", key);
+    match values.as_array() {
+        Some(values) if values.iter().all(Value::is_string) => values
+            .iter()
+            .filter_map(|value| value.as_str().map(str::to_owned))
+            .collect(),
+        _ => {
+            issues.push(TileError::InvalidEntity { id: id.to_owned(), reason });
+            Vec::new()
+        }
+    }
+}
+
+fn create_node(entity: &Value, index: usize, issues: &mut Vec<TileError>) -> Result<Node, TileError> {
     let id = match entity["@id"].as_str() {
         Some(id) => id.to_owned(),
-        _ =>  return Err(TileError::MissingID),
+        _ =>  return Err(TileError::MissingID(index)),
     };
 
     let lat = match entity["geo:lat"].as_f64() {
         Some(id) => id,
-        _ => return Err(TileError::MissingLatitude),
+        _ => return Err(TileError::MissingLatitude(id)),
     };
 
     let long = match entity["geo:long"].as_f64() {
         Some(id) => id,
-        _ => return Err(TileError::MissingLongitude),
+        _ => return Err(TileError::MissingLongitude(id)),
     };
 
+    let (tags, properties) = get_properties(entity, &["osm:hasTag"], &id, issues);
+    let undefined_tags = get_strings(entity, "osm:hasTag", &id, issues);
+
+    let mut node = Node::new(id, lat, long, tags, undefined_tags);
+    node.set_properties(properties);
+    Ok(node)
+}
+
+fn create_way(
+    entity: &Value,
+    index: usize,
+    speeds: &SpeedTable,
+    issues: &mut Vec<TileError>,
+) -> Result<Way, TileError> {
+    let id = match entity["@id"].as_str() {
+        Some(id) => id.to_owned(),
+        _ =>  return Err(TileError::MissingID(index)),
+    };
+
+    // contracted ways store their nodes together with the distances between them
+    let edges = &entity["osm:hasEdges"];
+    let nodes: Vec<String> = match entity["osm:hasNodes"].as_array().or_else(|| edges["osm:hasNodes"].as_array()) {
+        Some(nodes) if !nodes.is_empty() => {
+            let mut result = Vec::with_capacity(nodes.len());
+            for node in nodes {
+                match node.as_str() {
+                    Some(node) => result.push(node.to_owned()),
+                    None => {
+                        let reason = format!("invalid node reference {}", node);
+                        return Err(TileError::InvalidEntity { id, reason });
+                    }
+                }
+            }
+            result
+        },
+        _ => return Err(TileError::MissingNodes(id)),
+    };
+
+    let (tags, properties) = get_properties(entity, &["osm:hasNodes", "osm:hasEdges", "osm:hasTag"], &id, issues);
+    let undefined_tags = get_strings(entity, "osm:hasTag", &id, issues);
+
+    // the original value stays available as a tag,
+    // derived tiles also carry the limit it was interpreted as, see `write_derived_tile`
+    let mut max_speed = entity[INTERPRETED_MAXSPEED].as_f64();
+    if let (None, Some(value)) = (max_speed, tags.get("osm:maxspeed")) {
+        let speed = speeds.interpret(value);
+        if speed == MaxSpeed::Invalid {
+            let reason = format!("unparsable osm:maxspeed `{}`", value);
+            issues.push(TileError::InvalidEntity { id: id.clone(), reason });
+        }
+        max_speed = speed.get_limit();
+    }
+
+    let distances = edges["osm:hasWeights"]
+        .as_array()
+        .map(|weights| weights.iter().filter_map(Value::as_i64).collect());
+    let mut way = Way::new(id, nodes, distances, max_speed, tags, undefined_tags);
+    way.set_properties(properties);
+    Ok(way)
+}
+
+// the `osm` properties of an entity, except for the given lists: the values that profiles match against,
+// and the values that aren't plain strings, so that those can be written back as they were
+fn get_properties(
+    entity: &Value,
+    lists: &[&str],
+    id: &str,
+    issues: &mut Vec<TileError>,
+) -> (BTreeMap<String, String>, BTreeMap<String, Property>) {
     let mut tags = BTreeMap::new();
-    for (key, value) in entity.as_object().unwrap() {
-        if key != "osm:hasNodes" && key.starts_with("osm:") {
-            if value.is_string() {
-                tags.insert(key.to_string(), value.as_str().unwrap().to_owned());
-            } else {
-                // println!("{} {}", key, value);
+    let mut properties = BTreeMap::new();
+    if let Some(object) = entity.as_object() {
+        for (key, value) in object {
+            if !key.starts_with("osm:") || lists.contains(&key.as_str()) {
+                continue;
+            }
+
+            let property = match Property::from_json(value) {
+                Some(property) => property,
+                None => {
+                    let reason = format!("unsupported value for {}", key);
+                    issues.push(TileError::InvalidEntity { id: id.to_owned(), reason });
+                    continue;
+                }
+            };
+            if let Some(tag) = property.as_tag() {
+                tags.insert(key.to_string(), tag);
+            }
+            if !matches!(property, Property::String(_)) {
+                properties.insert(key.to_string(), property);
             }
         }
     }
+    (tags, properties)
+}
 
-    let mut undefined_tags = Vec::new();
-    if let Some(values) = entity.get("osm:hasTag") {
-        undefined_tags = values.as_array().unwrap()
-            .iter()
-            .map(|value| value.as_str().unwrap().to_owned())
-            .collect();
+// members are either plain references, or objects with a role
+fn create_member(member: &Value) -> Option<Member> {
+    if let Some(id) = member.as_str() {
+        return Some(Member::new(id.to_owned(), String::new()));
     }
 
-    Ok(Node::new(id, lat, long, tags, undefined_tags))
+    let id = member["@id"].as_str()?;
+    let role = match member.get("role").or_else(|| member.get("osm:role")) {
+        Some(role) => role.as_str()?,
+        None => "",
+    };
+    Some(Member::new(id.to_owned(), role.to_owned()))
 }
 
-fn create_way(entity: &Value) -> Result<Way, TileError> {
+fn create_relation(
+    entity: &Value,
+    index: usize,
+    issues: &mut Vec<TileError>,
+) -> Result<Relation, TileError> {
     let id = match entity["@id"].as_str() {
         Some(id) => id.to_owned(),
-        _ =>  return Err(TileError::MissingID),
+        _ =>  return Err(TileError::MissingID(index)),
     };
 
-    let nodes: Vec<String> = match entity["osm:hasNodes"].as_array() {
-        Some(nodes) => {
-            nodes.iter().map(|id| id.as_str().unwrap().to_string()).collect()
+    let members: Vec<Member> = match entity["osm:hasMembers"].as_array() {
+        Some(members) if !members.is_empty() => {
+            let mut result = Vec::with_capacity(members.len());
+            for member in members {
+                match create_member(member) {
+                    Some(member) => result.push(member),
+                    None => {
+                        let reason = format!("invalid member {}", member);
+                        return Err(TileError::InvalidEntity { id, reason });
+                    }
+                }
+            }
+            result
         },
-        _ => return Err(TileError::MissingNodes),
+        _ => return Err(TileError::MissingMembers(id)),
     };
 
-    let mut tags = BTreeMap::new();
-    for (key, value) in entity.as_object().unwrap() {
-        if key != "osm:hasNodes" && key.starts_with("osm") {
-            if value.is_string() {
-                tags.insert(key.to_string(), value.as_str().unwrap().to_owned());
-            } else if value.is_array() {
-                //
-            } else {
-                tags.insert(key.to_string(), value.to_string());
-            }
+    let (tags, properties) = get_properties(entity, &["osm:hasMembers", "osm:hasTag"], &id, issues);
+    let undefined_tags = get_strings(entity, "osm:hasTag", &id, issues);
+
+    let mut relation = Relation::new(id, members, tags, undefined_tags);
+    relation.set_properties(properties);
+    Ok(relation)
+}
+
+// where derived tiles get published, unless configured otherwise
+pub const DEFAULT_BASE_URL: &str = "https://tiles.openplanner.team/planet";
+
+// where a derived tile gets published, and how it was derived
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileDescription {
+    pub base_url: String, // without a trailing slash
+    pub task: String,
+    pub profile: Option<ProfileSource>,
+    pub concepts: BTreeSet<String>, // always declared in the context, even if a tile doesn't use them
+    pub context_url: Option<String>, // remote context that gets extended
+}
+
+impl TileDescription {
+    pub fn new(base_url: &str, task: &str, profile: Option<&Profile>) -> TileDescription {
+        TileDescription {
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            task: task.to_owned(),
+            profile: profile.map(|profile| profile.get_source().clone()),
+            concepts: profile.map(|profile| profile.get_used_concepts().into_iter().collect()).unwrap_or_default(),
+            context_url: None,
         }
     }
 
-    let mut undefined_tags = Vec::new();
-    if let Some(values) = entity.get("osm:hasTag") {
-        undefined_tags = values.as_array().unwrap()
-            .iter()
-            .map(|value| value.as_str().unwrap().to_owned())
-            .collect();
+    pub fn get_tile_iri(&self, coordinate: &TileCoordinate) -> String {
+        format!("{}/{}/{}/{}", self.base_url, coordinate.zoom, coordinate.x, coordinate.y)
     }
 
-    let mut max_speed = None;
-    if let Some(broken_string_value) = tags.get("osm:maxspeed") {
-        let float_value: f64 = broken_string_value.parse().unwrap();
-        max_speed = Some(float_value);
+    fn get_activity(&self) -> Value {
+        let mut activity = json!({
+            "@type": "prov:Activity",
+            "rdfs:label": self.task,
+        });
+        if let Some(profile) = &self.profile {
+            activity["prov:used"] = json!({
+                "@type": "prov:Entity",
+                "rdfs:label": profile.name,
+                "dcterms:identifier": format!("{:016x}", profile.hash),
+            });
+        }
+        activity
     }
 
-    Ok(Way::new(id, nodes, None, max_speed, tags, undefined_tags))
+    fn get_context(&self, tile: &Tile) -> Value {
+        let mut context = json!({
+            "dcterms:license":{"@type":"@id"},
+            "hydra:variableRepresentation":{"@type":"@id"},
+            "hydra:property":{"@type":"@id"},
+            "osm:hasNodes":{"@container":"@list","@type":"@id"},
+            "osm:hasMembers":{"@container":"@list","@type":"@id"},
+            "role":"osm:role"
+        });
+        for (prefix, iri) in CONTEXT_PREFIXES {
+            context[*prefix] = json!(iri);
+        }
+        for (term, definition) in get_tag_terms(tile, &self.concepts) {
+            context[term] = definition;
+        }
+
+        match &self.context_url {
+            Some(url) => json!([url, context]),
+            None => context,
+        }
+    }
+}
+
+fn is_iri(value: &str) -> bool {
+    if value.starts_with("http://") || value.starts_with("https://") {
+        return true;
+    }
+    match value.find(':') {
+        Some(index) => CONTEXT_PREFIXES.iter().any(|(prefix, _)| *prefix == &value[..index]),
+        None => false,
+    }
+}
+
+// tags whose values are all IRIs are coerced to @id, the others are redefined without a type,
+// so that a remote context can't turn them into IRIs either,
+// concepts of the profile are only declared if they're known to be IRI-valued
+fn get_tag_terms<'a>(tile: &'a Tile, concepts: &'a BTreeSet<String>) -> BTreeMap<&'a str, Value> {
+    let mut iri_valued: BTreeMap<&str, bool> = BTreeMap::new();
+    let tags = tile
+        .get_nodes()
+        .values()
+        .map(Node::get_tags)
+        .chain(tile.get_ways().values().map(Way::get_tags))
+        .chain(tile.get_relations().values().map(Relation::get_tags));
+    for (key, value) in tags.flatten() {
+        let entry = iri_valued.entry(key.as_str()).or_insert(true);
+        *entry = *entry && is_iri(value);
+    }
+    for concept in concepts {
+        if IRI_VALUED_TERMS.contains(&concept.as_str()) {
+            iri_valued.entry(concept.as_str()).or_insert(true);
+        }
+    }
+
+    iri_valued
+        .into_iter()
+        .map(|(key, iri)| {
+            if iri {
+                (key, json!({"@id": key, "@type": "@id"}))
+            } else {
+                (key, json!({"@id": key}))
+            }
+        })
+        .collect()
+}
+
+// typed values replace their tags, unless the tag was dropped
+fn insert_properties(
+    blob: &mut BTreeMap<String, Value>,
+    tags: &BTreeMap<String, String>,
+    properties: &BTreeMap<String, Property>,
+) {
+    for (key, value) in tags {
+        blob.insert(key.to_string(), json!(value));
+    }
+    for (key, property) in properties {
+        if property.as_tag().is_none() || tags.contains_key(key) {
+            blob.insert(key.to_string(), property.to_json());
+        }
+    }
 }
 
-pub fn write_derived_tile(tile: Tile, path: &str) -> Result<()> {
+pub fn write_derived_tile(tile: Tile, path: &str, description: &TileDescription) -> Result<()> {
     let mut graph: Vec<Value> = tile.get_nodes().values().map(|node| {
         let mut blob = BTreeMap::new();
         blob.insert("@type".to_owned(), json!("osm:Node"));
@@ -256,9 +721,7 @@ pub fn write_derived_tile(tile: Tile, path: &str) -> Result<()> {
             blob.insert("osm:hasTag".to_owned(), json!(node.get_undefined_tags()));
         }
 
-        for (key, value) in node.get_tags() {
-            blob.insert(key.to_string(), json!(value));
-        }
+        insert_properties(&mut blob, node.get_tags(), node.get_properties());
 
         json!(blob)
     }).collect();
@@ -280,62 +743,55 @@ pub fn write_derived_tile(tile: Tile, path: &str) -> Result<()> {
             blob.insert("osm:hasTag".to_owned(), json!(way.get_undefined_tags()));
         }
 
-        for (key, value) in way.get_tags() {
-            blob.insert(key.to_string(), json!(value));
-        }
-
-        if let Some(value) = way.get_max_speed() {
-            blob.insert("osm:maxspeed".to_owned(), json!(value));
+        // the original maxspeed tag is written as is, the limit it was interpreted as is written separately,
+        // so that the speed table of fetch_tiles or import_extract carries over to the derived tiles
+        insert_properties(&mut blob, way.get_tags(), way.get_properties());
+        if let Some(limit) = way.get_max_speed() {
+            blob.insert(INTERPRETED_MAXSPEED.to_owned(), json!(limit));
         }
 
         json!(blob)
     }).collect();
 
     graph.append(&mut ways);
-    let context = json!({
-            "tiles":"https://w3id.org/tree/terms#",
-            "hydra":"http://www.w3.org/ns/hydra/core#",
-            "osm":"https://w3id.org/openstreetmap/terms#",
-            "rdfs":"http://www.w3.org/2000/01/rdf-schema#",
-            "geo":"http://www.w3.org/2003/01/geo/wgs84_pos#",
-            "dcterms":"http://purl.org/dc/terms/",
-            "dcterms:license":{"@type":"@id"},
-            "hydra:variableRepresentation":{"@type":"@id"},
-            "hydra:property":{"@type":"@id"},
-            "osm:access":{"@type":"@id"},
-            "osm:barrier":{"@type":"@id"},
-            "osm:bicycle":{"@type":"@id"},
-            "osm:construction":{"@type":"@id"},
-            "osm:crossing":{"@type":"@id"},
-            "osm:cycleway":{"@type":"@id"},
-            "osm:footway":{"@type":"@id"},
-            "osm:highway":{"@type":"@id"},
-            "osm:motor_vehicle":{"@type":"@id"},
-            "osm:motorcar":{"@type":"@id"},
-            "osm:oneway_bicycle":{"@type":"@id"},
-            "osm:oneway":{"@type":"@id"},
-            "osm:smoothness":{"@type":"@id"},
-            "osm:surface":{"@type":"@id"},
-            "osm:tracktype":{"@type":"@id"},
-            "osm:vehicle":{"@type":"@id"},
-            "osm:hasNodes":{"@container":"@list","@type":"@id"},
-            "osm:hasMembers":{"@container":"@list","@type":"@id"}}
-            );
 
+    let mut relations: Vec<Value> = tile.get_relations().values().map(|relation| {
+        let mut blob = BTreeMap::new();
+        blob.insert("@type".to_owned(), json!("osm:Relation"));
+        blob.insert("@id".to_owned(), json!(relation.get_id()));
+        let members: Vec<Value> = relation
+            .get_members()
+            .iter()
+            .map(|member| json!({"@id": member.id, "role": member.role}))
+            .collect();
+        blob.insert("osm:hasMembers".to_owned(), json!(members));
+
+        if !relation.get_undefined_tags().is_empty() {
+            blob.insert("osm:hasTag".to_owned(), json!(relation.get_undefined_tags()));
+        }
+
+        insert_properties(&mut blob, relation.get_tags(), relation.get_properties());
+
+        json!(blob)
+    }).collect();
+
+    graph.append(&mut relations);
+    let coordinate = tile.get_coordinate();
     let file = json!({
-        "@context": context,
-        "@id":"https://tiles.openplanner.team/planet/14/8411/5485/",
-        "tiles:zoom":tile.get_coordinate().zoom,
-        "tiles:longitudeTile":tile.get_coordinate().x,
-        "tiles:latitudeTile":tile.get_coordinate().y,
+        "@context": description.get_context(&tile),
+        "@id":description.get_tile_iri(coordinate),
+        "tiles:zoom":coordinate.zoom,
+        "tiles:longitudeTile":coordinate.x,
+        "tiles:latitudeTile":coordinate.y,
+        "prov:wasGeneratedBy":description.get_activity(),
         "dcterms:isPartOf":{
-            "@id":"https://tiles.openplanner.team/planet/",
+            "@id":format!("{}/", description.base_url),
             "@type":"hydra:Collection",
             "dcterms:license":"http://opendatacommons.org/licenses/odbl/1-0/",
             "dcterms:rights":"http://www.openstreetmap.org/copyright",
             "hydra:search":{
                 "@type":"hydra:IriTemplate",
-                "hydra:template":"https://tiles.openplanner.team/planet/14/{x}/{y}",
+                "hydra:template":format!("{}/{}/{{x}}/{{y}}", description.base_url, coordinate.zoom),
                 "hydra:variableRepresentation":"hydra:BasicRepresentation",
                 "hydra:mapping":[{
                     "@type":"hydra:IriTemplateMapping",
@@ -361,6 +817,77 @@ pub fn write_derived_tile(tile: Tile, path: &str) -> Result<()> {
     Ok(())
 }
 
+#[derive(Deserialize)]
+struct VersionProbe {
+    magic: String,
+    version: u32,
+}
+
+fn check_version(path: &str, probe: Option<VersionProbe>) -> Result<(), TileError> {
+    match probe {
+        Some(VersionProbe { ref magic, .. }) if magic != MAGIC => {
+            Err(TileError::IncompatibleBinary(path.to_owned(), "not a weighted tile".to_owned()))
+        }
+        Some(VersionProbe { version, .. }) if version != SCHEMA_VERSION => {
+            let reason = format!("schema version {}, expected {}", version, SCHEMA_VERSION);
+            Err(TileError::IncompatibleBinary(path.to_owned(), reason))
+        }
+        None => {
+            let reason = "no header, written by an older version".to_owned();
+            Err(TileError::IncompatibleBinary(path.to_owned(), reason))
+        }
+        _ => Ok(()),
+    }
+}
+
+// checks a flexbuffer encoded `TileHeader` before committing to the full layout
+pub fn check_binary_header(path: &str, header: &[u8]) -> Result<(), TileError> {
+    match flexbuffers::from_slice(header) {
+        Ok(probe) => check_version(path, Some(probe)),
+        Err(_) => Err(TileError::InvalidBinary(path.to_owned())),
+    }
+}
+
+// reads the flexbuffers, compact and CSR encodings
+pub fn load_binary_tile(path: &str) -> Result<WeightedTile, TileError> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        _ => return Err(TileError::NotAFile(path.to_owned())),
+    };
+
+    if is_compact_tile(&data) {
+        return decode_compact_tile(path, &data);
+    }
+
+    if is_csr_graph(&data) {
+        let graph = CsrGraph::new(&data).map_err(|e| match e {
+            CsrError::UnsupportedVersion(_) => TileError::IncompatibleBinary(path.to_owned(), e.to_string()),
+            _ => TileError::InvalidBinary(path.to_owned()),
+        })?;
+        check_binary_header(path, graph.get_raw_header())?;
+        return match flexbuffers::from_slice(graph.get_raw_header()) {
+            Ok(header) => Ok(graph.to_weighted_tile(header)),
+            Err(_) => Err(TileError::InvalidBinary(path.to_owned())),
+        };
+    }
+
+    // check the header before committing to the full layout
+    #[derive(Deserialize)]
+    struct HeaderProbe {
+        header: Option<VersionProbe>,
+    }
+
+    match flexbuffers::from_slice::<HeaderProbe>(&data) {
+        Ok(probe) => check_version(path, probe.header)?,
+        Err(_) => return Err(TileError::InvalidBinary(path.to_owned())),
+    }
+
+    match flexbuffers::from_slice(&data) {
+        Ok(tile) => Ok(tile),
+        Err(_) => Err(TileError::InvalidBinary(path.to_owned())),
+    }
+}
+
 pub fn write_flexbuffers_tile(tile: WeightedTile, path: &str) {
     let mut s = FlexbufferSerializer::new();
     tile.serialize(&mut s).unwrap();
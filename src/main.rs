@@ -4,15 +4,23 @@ extern crate clap;
 #[macro_use] extern crate cached;
 
 use crate::tasks::load_tile::fetch_tile;
-use crate::io::tiles::write_flexbuffers_tile;
-use crate::tasks::reduce_binary::create_binary_tile;
-use crate::io::get_binary_tile_path;
-use crate::io::profile::load_bicycle_profile;
+use crate::io::tiles::{load_binary_tile, write_flexbuffers_tile, ParseMode};
+use crate::io::compact_tile::write_compact_tile;
+use crate::io::csr_graph::write_csr_tile;
+use crate::tasks::inspect_binary::describe_binary_tile;
+use crate::tasks::reduce_binary::{create_binary_tile, BinaryTileOptions};
+use crate::io::{get_binary_tile_path, get_geojson_tile_path};
+use crate::tasks::export_geojson::{export_geojson, GeoJsonOptions};
+use crate::util::maxspeed::SpeedTable;
+use crate::tasks::import_extract::cut_extract;
 use crate::tasks::merge_tiles::create_merged_tile;
 use crate::tasks::reduce_profile::create_profile_tile;
 use crate::tasks::reduce_transit::create_indirect_transit_tile;
 use crate::tasks::reduce_transit::create_transit_tile;
-use clap::{App, load_yaml};
+use clap::{App, ArgMatches, load_yaml};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
 
 mod entities;
 mod io;
@@ -25,58 +33,162 @@ extern crate serde;
 extern crate serde_json;
 
 use crate::io::get_tile_path;
-use crate::io::profile::load_car_profile;
-use crate::io::profile::load_pedestrian_profile;
-use crate::io::tiles::write_derived_tile;
+use crate::io::profile::{find_profile_path, load_named_profile, load_profile};
+use crate::io::profile_validation::validate_profile;
+use crate::io::tiles::{write_derived_tile, TileDescription, TileError, DEFAULT_BASE_URL};
 use crate::util::get_tile_coords;
+use crate::io::area::load_area_file;
 use indicatif::{ProgressBar, ProgressStyle};
 use rayon::prelude::*;
 use entities::tile_coord::TileCoordinate;
-use cli::{area::Areas, profile::Profiles};
+use entities::area::Area;
+use entities::compiled_profile::CompiledProfile;
+use cli::area::Areas;
+use cli::metric::parse_metrics;
+
+fn load_speed_table(matches: Option<&ArgMatches>) -> SpeedTable {
+    match matches.and_then(|m| m.value_of("maxspeed_table")) {
+        Some(path) => SpeedTable::load(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }),
+        None => SpeedTable::default(),
+    }
+}
+
+type Warnings = Mutex<Vec<(TileCoordinate, TileError)>>;
+
+fn add_warnings(warnings: &Warnings, id: &TileCoordinate, tile_warnings: Vec<TileError>) {
+    let mut warnings = warnings.lock().unwrap();
+    warnings.extend(tile_warnings.into_iter().map(|warning| (*id, warning)));
+}
+
+// entities that couldn't be parsed are skipped, they're reported once all tiles are done
+fn report_warnings(warnings: Warnings) {
+    let mut warnings = warnings.into_inner().unwrap();
+    warnings.sort_by_key(|(id, _)| *id);
+    for (id, warning) in warnings {
+        eprintln!("{}/{}/{}: {}", id.zoom, id.x, id.y, warning);
+    }
+}
+
+// subcommands negate the requirements of the top-level arguments, so these are checked here
+fn get_required<'a>(matches: &'a ArgMatches, name: &str, flag: &str) -> &'a str {
+    matches.value_of(name).unwrap_or_else(|| {
+        eprintln!("error: {} is required for this subcommand", flag);
+        std::process::exit(1);
+    })
+}
 
 fn main() {
     let yaml = load_yaml!("cli.yaml");
     let matches = App::from(yaml).get_matches();
 
-    let zoom = matches
-        .value_of("zoom")
-        .unwrap()
+    // subcommands that don't process a set of tiles
+    if let ("inspect_binary", Some(sub_matches)) = matches.subcommand() {
+        let path = sub_matches.value_of("file").unwrap();
+        let samples = sub_matches
+            .value_of("samples")
+            .unwrap()
+            .parse::<usize>()
+            .expect("Invalid number of samples");
+
+        match load_binary_tile(path) {
+            Ok(tile) => print!("{}", describe_binary_tile(&tile, samples)),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let ("validate_profile", Some(sub_matches)) = matches.subcommand() {
+        let search_dirs: Vec<&str> = sub_matches
+            .values_of("profile_dir")
+            .map(|dirs| dirs.collect())
+            .unwrap_or_default();
+        let path = match sub_matches.value_of("profile_file") {
+            Some(path) => PathBuf::from(path),
+            None => find_profile_path(sub_matches.value_of("profile").unwrap(), &search_dirs)
+                .unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }),
+        };
+        let data = fs::read_to_string(&path).unwrap_or_else(|_| {
+            eprintln!("Invalid file path `{}`", path.display());
+            std::process::exit(1);
+        });
+
+        let issues = validate_profile(&data);
+        for issue in issues.iter() {
+            println!("{}: {}", path.display(), issue);
+        }
+
+        let errors = issues.iter().filter(|issue| issue.is_error()).count();
+        println!("{} error(s), {} warning(s)", errors, issues.len() - errors);
+        std::process::exit(if errors > 0 { 1 } else { 0 });
+    }
+
+    let zoom = get_required(&matches, "zoom", "--zoom")
         .parse::<u32>()
         .expect("Invalid zoom level");
 
-    let area = matches.value_of_t("area").unwrap_or_else(|e| e.exit());
-    let [lats, lons] = match area {
-        Areas::London => [[51.2424, 51.7334], [-0.5637, 0.3114]], // very dense
-        Areas::Belgium => [[49.421, 51.532], [2.4153, 6.5626]], // medium dense
-        Areas::Pyrenees => [[41.8872, 43.4263], [-1.9133, 3.3382]], // sparse
-        Areas::Dummy => [[51.15, 51.25], [4.40, 4.5]], // tiny piece of belgium
+    let area = if let Some(path) = matches.value_of("area_file") {
+        load_area_file(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        })
+    } else if matches.is_present("bbox") {
+        Area::BoundingBox(matches.value_of_t("bbox").unwrap_or_else(|e| e.exit()))
+    } else {
+        get_required(&matches, "area", "one of --area, --bbox or --area-file");
+        let area: Areas = matches.value_of_t("area").unwrap_or_else(|e| e.exit());
+        Area::BoundingBox(area.get_bounding_box())
     };
 
-    let input_dir = matches.value_of("input").unwrap();
-    let output_dir = matches.value_of("output").unwrap();
+    let input_dir = get_required(&matches, "input", "--input_dir");
+    let output_dir = get_required(&matches, "output", "--output_dir");
+    let base_url = matches.value_of("base_url").unwrap_or(DEFAULT_BASE_URL);
 
     let (sub_name, sub_matches) = matches.subcommand();
-    let profile = sub_matches
-        .and_then(|m| {
-            let a = m.value_of_t("profile");
-            match a {
-                Ok(Profiles::Car) => Some(load_car_profile().unwrap()),
-                Ok(Profiles::Bicycle) => Some(load_bicycle_profile().unwrap()),
-                Ok(Profiles::Pedestrian) => Some(load_pedestrian_profile().unwrap()),
-                Err(_) => None,
+    let profile = sub_matches.and_then(|m| {
+        let search_dirs: Vec<&str> = m
+            .values_of("profile_dir")
+            .map(|dirs| dirs.collect())
+            .unwrap_or_default();
+
+        let result = if let Some(path) = m.value_of("profile_file") {
+            load_profile(path)
+        } else if let Some(name) = m.value_of("profile") {
+            load_named_profile(name, &search_dirs)
+        } else {
+            return None;
+        };
+
+        match result {
+            Ok(profile) => Some(CompiledProfile::new(profile)),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
             }
-        });
+        }
+    });
 
     let padding_level = sub_matches
         .and_then(|m| m .value_of("padding"))
         .map(|v| v.parse::<u32>().expect("Invalid padding zoom level"));
 
+    let mut description = TileDescription::new(base_url, sub_name, profile.as_ref().map(|p| p.get_profile()));
+    description.context_url = matches.value_of("context_url").map(str::to_owned);
+
     match sub_name {
         "reduce_profile" => {
             let profile = profile.unwrap();
 
-            println!("Used concepts: {:?}", profile.get_used_concepts());
-            let todo = get_tile_coords(lats, lons, zoom);
+            println!("Used concepts: {:?}", profile.get_profile().get_used_concepts());
+            let todo = get_tile_coords(&area, zoom);
             let progress = ProgressBar::new(todo.len() as u64);
             progress.set_style(
                 ProgressStyle::default_bar()
@@ -84,19 +196,22 @@ fn main() {
                     .progress_chars("█▓░"),
             );
 
+            let warnings = Mutex::new(Vec::new());
             todo.par_iter().for_each(|id| {
                 let profile_tile_path = get_tile_path(output_dir, id);
-                let profile_tile = create_profile_tile(input_dir, id, &profile);
-                write_derived_tile(profile_tile, &profile_tile_path).unwrap();
+                let (profile_tile, tile_warnings) = create_profile_tile(input_dir, id, &profile);
+                write_derived_tile(profile_tile, &profile_tile_path, &description).unwrap();
+                add_warnings(&warnings, id, tile_warnings);
                 progress.inc(1);
             });
 
             progress.finish();
+            report_warnings(warnings);
         }
         "reduce_transit" => {
             let profile = profile.unwrap();
 
-            let todo = get_tile_coords(lats, lons, zoom);
+            let todo = get_tile_coords(&area, zoom);
             let progress = ProgressBar::new(todo.len() as u64);
             progress.set_style(
                 ProgressStyle::default_bar()
@@ -104,20 +219,23 @@ fn main() {
                     .progress_chars("█▓░"),
             );
 
+            let warnings = Mutex::new(Vec::new());
             todo.par_iter().for_each(|id| {
                 let profile_tile_path = get_tile_path(output_dir, id);
-                let profile_tile = create_transit_tile(input_dir, id, &profile);
-                write_derived_tile(profile_tile, &profile_tile_path).unwrap();
+                let (profile_tile, tile_warnings) = create_transit_tile(input_dir, id, &profile);
+                write_derived_tile(profile_tile, &profile_tile_path, &description).unwrap();
+                add_warnings(&warnings, id, tile_warnings);
                 progress.inc(1);
             });
 
             progress.finish();
+            report_warnings(warnings);
         }
         "reduce_padded_transit" => {
             let profile = profile.unwrap();
             let padding_level = padding_level.unwrap();
 
-            let todo = get_tile_coords(lats, lons, zoom);
+            let todo = get_tile_coords(&area, zoom);
             let progress = ProgressBar::new(todo.len() as u64);
             progress.set_style(
                 ProgressStyle::default_bar()
@@ -125,24 +243,37 @@ fn main() {
                     .progress_chars("█▓░"),
             );
 
+            let warnings = Mutex::new(Vec::new());
             todo.par_iter().for_each(|id| {
                 let profile_tile_path = get_tile_path(output_dir, id);
-                let profile_tile = create_indirect_transit_tile(
+                let (profile_tile, tile_warnings) = create_indirect_transit_tile(
                     input_dir,
                     padding_level,
                     id,
                     &profile,
                 );
-                write_derived_tile(profile_tile, &profile_tile_path).unwrap();
+                write_derived_tile(profile_tile, &profile_tile_path, &description).unwrap();
+                add_warnings(&warnings, id, tile_warnings);
                 progress.inc(1);
             });
 
             progress.finish();
+            report_warnings(warnings);
         }
         "reduce_binary" => {
             let profile = profile.unwrap();
+            let sub_matches = sub_matches.unwrap();
+            let encoding = sub_matches.value_of("encoding").unwrap();
+            let metrics = parse_metrics(sub_matches.values_of("metrics").into_iter().flatten());
+            let options = BinaryTileOptions {
+                way_references: sub_matches.is_present("way_references"),
+                metrics: metrics.unwrap_or_else(|e| {
+                    eprintln!("error: {}", e);
+                    std::process::exit(1);
+                }),
+            };
 
-            let todo = get_tile_coords(lats, lons, zoom);
+            let todo = get_tile_coords(&area, zoom);
             let progress = ProgressBar::new(todo.len() as u64);
             progress.set_style(
                 ProgressStyle::default_bar()
@@ -150,17 +281,38 @@ fn main() {
                     .progress_chars("█▓░"),
             );
 
+            let warnings = Mutex::new(Vec::new());
+            let failed = Mutex::new(Vec::new());
             todo.par_iter().for_each(|id| {
                 let merged_tile_path = get_binary_tile_path(output_dir, id);
-                let binary_tile = create_binary_tile(input_dir, id, &profile);
-                write_flexbuffers_tile(binary_tile, &merged_tile_path);
+                let (binary_tile, tile_warnings) = create_binary_tile(input_dir, id, &profile, &options);
+                match encoding {
+                    "compact" => write_compact_tile(binary_tile, &merged_tile_path),
+                    "csr" => {
+                        // e.g. tiles with ids that aren't numeric, the other tiles are still written
+                        if let Err(e) = write_csr_tile(binary_tile, &merged_tile_path) {
+                            failed.lock().unwrap().push((*id, e));
+                        }
+                    }
+                    _ => write_flexbuffers_tile(binary_tile, &merged_tile_path),
+                }
+                add_warnings(&warnings, id, tile_warnings);
                 progress.inc(1);
             });
 
             progress.finish();
+            report_warnings(warnings);
+            let mut failed = failed.into_inner().unwrap();
+            failed.sort_by_key(|(id, _)| *id);
+            for (id, e) in failed.iter() {
+                eprintln!("{}/{}/{}: {}", id.zoom, id.x, id.y, e);
+            }
+            if !failed.is_empty() {
+                eprintln!("Failed to write {} tiles", failed.len());
+            }
         }
         "merge" => {
-            let todo = get_tile_coords(lats, lons, zoom);
+            let todo = get_tile_coords(&area, zoom);
             let progress = ProgressBar::new(todo.len() as u64);
             progress.set_style(
                 ProgressStyle::default_bar()
@@ -168,18 +320,48 @@ fn main() {
                     .progress_chars("█▓░"),
             );
 
+            let warnings = Mutex::new(Vec::new());
             todo.par_iter().for_each(|id| {
                 let merged_tile_path = get_tile_path(output_dir, id);
                 let c = id.get_children();
-                let merged_tile = create_merged_tile(input_dir, &c, id);
-                write_derived_tile(merged_tile, &merged_tile_path).unwrap();
+                let (merged_tile, tile_warnings) = create_merged_tile(input_dir, &c, id);
+                write_derived_tile(merged_tile, &merged_tile_path, &description).unwrap();
+                add_warnings(&warnings, id, tile_warnings);
+                progress.inc(1);
+            });
+
+            progress.finish();
+            report_warnings(warnings);
+        }
+        "export_geojson" => {
+            let sub_matches = sub_matches.unwrap();
+            let options = GeoJsonOptions {
+                edge_nodes: sub_matches.is_present("edge_nodes"),
+                necessary_nodes: profile.as_ref().filter(|_| sub_matches.is_present("necessary_nodes")),
+            };
+
+            let todo = get_tile_coords(&area, zoom);
+            let progress = ProgressBar::new(todo.len() as u64);
+            progress.set_style(
+                ProgressStyle::default_bar()
+                    .template("Exporting tiles [{elapsed_precise}] {wide_bar:.cyan/blue} {pos:>7}/{len:7} {msg}")
+                    .progress_chars("█▓░"),
+            );
+
+            let warnings = Mutex::new(Vec::new());
+            todo.par_iter().for_each(|id| {
+                // missing tiles are skipped
+                if let Ok(tile_warnings) = export_geojson(input_dir, id, &get_geojson_tile_path(output_dir, id), &options) {
+                    add_warnings(&warnings, id, tile_warnings);
+                }
                 progress.inc(1);
             });
 
             progress.finish();
+            report_warnings(warnings);
         }
         "fetch_tiles" => {
-            let todo = get_tile_coords(lats, lons, zoom);
+            let todo = get_tile_coords(&area, zoom);
             let progress = ProgressBar::new(todo.len() as u64);
             progress.set_style(
                 ProgressStyle::default_bar()
@@ -187,19 +369,62 @@ fn main() {
                     .progress_chars("█▓░"),
             );
 
+            let mode = match sub_matches.map_or(false, |m| m.is_present("strict")) {
+                true => ParseMode::Strict,
+                false => ParseMode::Lenient,
+            };
+
+            let speeds = load_speed_table(sub_matches);
+            let warnings = Mutex::new(Vec::new());
+            let rejected = Mutex::new(Vec::new());
             let failed: Vec<TileCoordinate> = todo.par_iter().filter_map(|id| {
-                if let Ok(()) = fetch_tile(input_dir, output_dir, id) {
-                    progress.inc(1);
-                    return None
+                match fetch_tile(input_dir, output_dir, id, mode, &speeds, &description) {
+                    Ok(tile_warnings) => {
+                        add_warnings(&warnings, id, tile_warnings);
+                        progress.inc(1);
+                        None
+                    }
+                    Err(e) => {
+                        // tiles that were fetched but couldn't be parsed, as opposed to missing ones
+                        if let Ok(error) = e.downcast::<TileError>() {
+                            add_warnings(&rejected, id, vec![error]);
+                        }
+                        Some(*id)
+                    }
                 }
-                
-               Some(*id)
             }).collect();
 
+            progress.finish();
+            report_warnings(warnings);
+            report_warnings(rejected);
             eprintln!("Failed to get {} tiles\nThis might be ok, some tiles don't exist", failed.len());
+        },
+        "import_extract" => {
+            let todo = get_tile_coords(&area, zoom);
+            let speeds = load_speed_table(sub_matches);
+            let progress = ProgressBar::new(todo.len() as u64);
+            progress.set_style(
+                ProgressStyle::default_bar()
+                    .template("Writing tiles [{elapsed_precise}] {wide_bar:.cyan/blue} {pos:>7}/{len:7} {msg}")
+                    .progress_chars("█▓░"),
+            );
+
+            // tiles without any highways aren't written, so the bar only counts the ones that are
+            cut_extract(input_dir, &todo, &speeds, |tiles, total| {
+                progress.set_length(total as u64);
+                tiles.into_par_iter().for_each(|tile| {
+                    let path = get_tile_path(output_dir, tile.get_coordinate());
+                    write_derived_tile(tile, &path, &description).unwrap();
+                    progress.inc(1);
+                });
+            })
+            .unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
 
             progress.finish();
-        },
+        }
         _ => unreachable!(),
     };
 }
@@ -207,46 +432,1182 @@ fn main() {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use io::tiles::load_tile;
+    use io::profile::{load_bicycle_profile, load_car_profile};
+    use io::tiles::{load_tile, parse_tile, read_tile, ParseMode, TileError};
+    use std::io::Read;
+    use std::path::Path;
+    use std::time::Instant;
+    use io::compact_tile::{decode_compact_tile, encode_compact_tile, COMPACT_MAGIC};
+    use io::csr_graph::{encode_csr_graph, CsrError, CsrGraph};
+    use entities::weighted_tile::Metric;
+
+    // a way without nodes, to evaluate profile rules against
+    fn tagged_way(tags: &[(&str, &str)]) -> entities::way::Way {
+        let tags = tags.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        entities::way::Way::new("way".to_owned(), vec![], None, None, tags, vec![])
+    }
 
     #[test]
     fn test_parse() {
         let coord = TileCoordinate::new(8345, 5495, 14);
-        let tile = load_tile(&coord, "./test_data").unwrap();
+        let tile = load_tile(&coord, "./test_data").unwrap().0;
         assert_eq!(tile.get_nodes().len(), 725);
         assert_eq!(tile.get_ways().len(), 176);
     }
 
+    fn read_test_tile(coord: &TileCoordinate) -> String {
+        let file = fs::File::open(get_tile_path("./test_data", coord)).unwrap();
+        let mut data = String::new();
+        flate2::read::GzDecoder::new(file).read_to_string(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_streaming_parser() {
+        let speeds = SpeedTable::default();
+        for x in 8344..=8348 {
+            for y in 5494..=5496 {
+                let coord = TileCoordinate::new(x, y, 14);
+                if !Path::new(&get_tile_path("./test_data", &coord)).exists() {
+                    continue;
+                }
+                let streamed = load_tile(&coord, "./test_data").unwrap().0;
+                let data = read_test_tile(&coord);
+                let (parsed, warnings) = parse_tile(&coord, data, ParseMode::Strict, &speeds).unwrap();
+                assert!(warnings.is_empty());
+                assert_eq!(streamed, parsed);
+            }
+        }
+
+        let coord = TileCoordinate::new(8345, 5495, 14);
+        let data = r#"{"@context": {}, "@graph": [{"@type": "osm:Node", "@id": "n1", "geo:lat": 1.0, "geo:long": 2.0}]}"#;
+        let (tile, _) = read_tile(&coord, data.as_bytes(), ParseMode::Strict, &speeds).unwrap();
+        assert_eq!(tile.get_nodes().len(), 1);
+        let result = read_tile(&coord, &b"{\"@context\": {}}"[..], ParseMode::Lenient, &speeds);
+        assert!(matches!(result, Err(TileError::NotJson)));
+        let result = read_tile(&coord, &b"{\"@graph\": ["[..], ParseMode::Lenient, &speeds);
+        assert!(matches!(result, Err(TileError::NotJson)));
+
+        let path = std::env::temp_dir().join("preprocess_broken/14/8345");
+        fs::create_dir_all(&path).unwrap();
+        fs::write(path.join("5495.jsonld.zip"), b"not gzip").unwrap();
+        let root = std::env::temp_dir().join("preprocess_broken");
+        let result = load_tile(&coord, root.to_str().unwrap());
+        assert!(matches!(result, Err(TileError::InvalidFile(_))));
+    }
+
+    #[test]
+    fn test_invalid_entities() {
+        let speeds = SpeedTable::default();
+        let coord = TileCoordinate::new(8345, 5495, 14);
+        let data = r#"{"@graph": [
+            {"@type": "osm:Node", "@id": "n1", "geo:lat": 1.0, "geo:long": 2.0},
+            {"@type": "osm:Node", "@id": "n2", "geo:lat": 1.0, "geo:long": 2.1},
+            {"@id": "n3", "geo:lat": 1.0, "geo:long": 2.2},
+            {"@type": "osm:Node", "@id": "n4", "geo:lat": 1.0},
+            {"@type": "osm:Way", "@id": "w1", "osm:hasNodes": ["n1", "n2"], "osm:maxspeed": "fast"},
+            {"@type": "osm:Way", "@id": "w2", "osm:hasNodes": ["n1", 2]},
+            {"@type": "osm:Way", "osm:hasNodes": ["n1", "n2"]},
+            {"@type": "osm:Way", "@id": "w3", "osm:hasNodes": []},
+            {"@type": "osm:Way", "@id": "w4", "osm:hasNodes": ["n1", "n4"], "osm:highway": "osm:Primary"}
+        ]}"#;
+
+        let (tile, warnings) = read_tile(&coord, data.as_bytes(), ParseMode::Lenient, &speeds).unwrap();
+        assert_eq!(tile.get_nodes().len(), 2);
+        assert_eq!(tile.get_ways().len(), 1);
+        let way = &tile.get_ways()["w1"];
+        assert_eq!(way.get_max_speed(), &None);
+        assert_eq!(way.get_tags()["osm:maxspeed"], "fast");
+
+        let messages: Vec<String> = warnings.iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Entity `n3` has no @type",
+                "Node `n4` has no longitude",
+                "Invalid entity `w1`: unparsable osm:maxspeed `fast`",
+                "Invalid entity `w2`: invalid node reference 2",
+                "Entity #6 of the graph has no @id",
+                "Way `w3` has no nodes",
+                "Invalid entity `w4`: missing node `n4`",
+            ]
+        );
+
+        let (parsed, _) = parse_tile(&coord, data.to_string(), ParseMode::Lenient, &speeds).unwrap();
+        assert_eq!(parsed, tile);
+
+        let result = read_tile(&coord, data.as_bytes(), ParseMode::Strict, &speeds);
+        assert!(matches!(result, Err(TileError::MissingType(ref id)) if id == "n3"));
+
+        // the tasks pass the warnings on, instead of silently skipping the entities,
+        // and don't trip over the way that lost one of its nodes
+        let root = std::env::temp_dir().join("preprocess_invalid_entities");
+        let root = root.to_str().unwrap();
+        let file = fs::File::create(get_tile_path(root, &coord)).unwrap();
+        let mut encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, data.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        let car_profile = CompiledProfile::new(load_car_profile().unwrap());
+        assert_eq!(create_profile_tile(root, &coord, &car_profile).1.len(), messages.len());
+        assert_eq!(create_transit_tile(root, &coord, &car_profile).1.len(), messages.len());
+        let options = BinaryTileOptions::default();
+        assert_eq!(create_binary_tile(root, &coord, &car_profile, &options).1.len(), messages.len());
+        let parent = TileCoordinate::new(4172, 2747, 13);
+        assert_eq!(create_merged_tile(root, &parent.get_children(), &parent).1.len(), messages.len());
+    }
+
+    #[test]
+    fn test_typed_properties() {
+        let root = std::env::temp_dir().join("preprocess_properties");
+        let root = root.to_str().unwrap();
+        let coord = TileCoordinate::new(8344, 5494, 14);
+        let data = r#"{"@graph": [
+            {"@type": "osm:Node", "@id": "n1", "geo:lat": 50.85, "geo:long": 3.31,
+             "osm:ele": 12, "osm:wheelchair": true, "osm:colours": ["osm:Red", 2], "osm:note": null},
+            {"@type": "osm:Node", "@id": "n2", "geo:lat": 50.85, "geo:long": 3.32},
+            {"@type": "osm:Way", "@id": "w1", "osm:hasNodes": ["n1", "n2"], "osm:highway": "osm:Primary",
+             "osm:lanes": 2, "osm:width": 2.5},
+            {"@type": "osm:Relation", "@id": "r1", "osm:hasMembers": [{"@id": "w1", "role": "from"}],
+             "osm:type": "restriction", "osm:priority": 3}
+        ]}"#;
+
+        let (tile, warnings) = read_tile(&coord, data.as_bytes(), ParseMode::Lenient, &SpeedTable::default()).unwrap();
+        let messages: Vec<String> = warnings.iter().map(|w| w.to_string()).collect();
+        assert_eq!(messages, vec!["Invalid entity `n1`: unsupported value for osm:note"]);
+
+        // numbers and booleans can still be matched by profiles, lists can't
+        let node = &tile.get_nodes()["n1"];
+        assert_eq!(node.get_tags()["osm:ele"], "12");
+        assert_eq!(node.get_tags()["osm:wheelchair"], "true");
+        assert!(!node.get_tags().contains_key("osm:colours"));
+        assert_eq!(node.get_properties().len(), 3);
+        let way = &tile.get_ways()["w1"];
+        assert_eq!(way.get_tags()["osm:width"], "2.5");
+        assert_eq!(way.get_properties().keys().collect::<Vec<_>>(), vec!["osm:lanes", "osm:width"]);
+        let relation = &tile.get_relations()["r1"];
+        assert_eq!(relation.get_tags()["osm:priority"], "3");
+        assert_eq!(relation.get_properties().keys().collect::<Vec<_>>(), vec!["osm:priority"]);
+
+        let description = TileDescription::new(DEFAULT_BASE_URL, "test", None);
+        write_derived_tile(tile.clone(), &get_tile_path(root, &coord), &description).unwrap();
+        let file = fs::File::open(get_tile_path(root, &coord)).unwrap();
+        let mut data = String::new();
+        flate2::read::GzDecoder::new(file).read_to_string(&mut data).unwrap();
+        let written: serde_json::Value = serde_json::from_str(&data).unwrap();
+        let graph = written["@graph"].as_array().unwrap();
+        let node = graph.iter().find(|entity| entity["@id"] == "n1").unwrap();
+        assert_eq!(node["osm:ele"], serde_json::json!(12));
+        assert_eq!(node["osm:wheelchair"], serde_json::json!(true));
+        assert_eq!(node["osm:colours"], serde_json::json!(["osm:Red", 2]));
+        let way = graph.iter().find(|entity| entity["@id"] == "w1").unwrap();
+        assert_eq!(way["osm:lanes"], serde_json::json!(2));
+        assert_eq!(way["osm:width"], serde_json::json!(2.5));
+        let relation = graph.iter().find(|entity| entity["@id"] == "r1").unwrap();
+        assert_eq!(relation["osm:priority"], serde_json::json!(3));
+
+        assert_eq!(load_tile(&coord, root).unwrap().0, tile);
+    }
+
+    #[test]
+    fn test_relations() {
+        let root = std::env::temp_dir().join("preprocess_relations");
+        let root = root.to_str().unwrap();
+        let coord = TileCoordinate::new(8344, 5494, 14);
+        let data = r#"{"@graph": [
+            {"@type": "osm:Node", "@id": "n1", "geo:lat": 50.85, "geo:long": 3.31},
+            {"@type": "osm:Node", "@id": "n2", "geo:lat": 50.85, "geo:long": 3.32},
+            {"@type": "osm:Node", "@id": "n3", "geo:lat": 50.86, "geo:long": 3.32},
+            {"@type": "osm:Way", "@id": "w1", "osm:hasNodes": ["n1", "n2"], "osm:highway": "osm:Primary"},
+            {"@type": "osm:Way", "@id": "w2", "osm:hasNodes": ["n2", "n3"], "osm:highway": "osm:Primary"},
+            {"@type": "osm:Relation", "@id": "r1", "osm:type": "restriction", "osm:restriction": "no_left_turn",
+             "osm:hasMembers": [{"@id": "w1", "role": "from"}, {"@id": "n2", "role": "via"}, {"@id": "w2", "role": "to"}]},
+            {"@type": "osm:Relation", "@id": "r2", "osm:type": "route", "osm:hasMembers": ["w9"]},
+            {"@type": "osm:Relation", "@id": "r3", "osm:hasMembers": []}
+        ]}"#;
+
+        let speeds = SpeedTable::default();
+        let (tile, warnings) = read_tile(&coord, data.as_bytes(), ParseMode::Lenient, &speeds).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(matches!(warnings[0], TileError::MissingMembers(ref id) if id == "r3"));
+
+        let restriction = &tile.get_relations()["r1"];
+        let roles: Vec<&str> = restriction.get_members().iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, vec!["from", "via", "to"]);
+        assert_eq!(restriction.get_tags()["osm:restriction"], "no_left_turn");
+        assert_eq!(tile.get_relations()["r2"].get_members()[0].role, "");
+
+        let description = TileDescription::new(DEFAULT_BASE_URL, "test", None);
+        write_derived_tile(tile.clone(), &get_tile_path(root, &coord), &description).unwrap();
+        assert_eq!(load_tile(&coord, root).unwrap().0, tile);
+
+        // relations without any retained members are dropped
+        let car_profile = CompiledProfile::new(load_car_profile().unwrap());
+        let reduced = create_profile_tile(root, &coord, &car_profile).0;
+        assert_eq!(reduced.get_relations().keys().collect::<Vec<_>>(), vec!["r1"]);
+
+        let parent = TileCoordinate::new(4172, 2747, 13);
+        let merged = create_merged_tile(root, &parent.get_children(), &parent).0;
+        assert_eq!(merged.get_relations().keys().collect::<Vec<_>>(), vec!["r1"]);
+        assert_eq!(merged.get_relations()["r1"], *restriction);
+    }
+
+    #[test]
+    fn test_tile_description() {
+        let root = std::env::temp_dir().join("preprocess_description");
+        let root = root.to_str().unwrap();
+        let coord = TileCoordinate::new(8345, 5495, 14);
+        let tile = load_tile(&coord, "./test_data").unwrap().0;
+        let profile = load_car_profile().unwrap();
+        let description = TileDescription::new("https://example.org/car/", "reduce_profile", Some(&profile));
+        write_derived_tile(tile.clone(), &get_tile_path(root, &coord), &description).unwrap();
+
+        let file = fs::File::open(get_tile_path(root, &coord)).unwrap();
+        let mut data = String::new();
+        flate2::read::GzDecoder::new(file).read_to_string(&mut data).unwrap();
+        let written: serde_json::Value = serde_json::from_str(&data).unwrap();
+        assert_eq!(written["@id"], "https://example.org/car/14/8345/5495");
+        assert_eq!(written["dcterms:isPartOf"]["@id"], "https://example.org/car/");
+        assert_eq!(
+            written["dcterms:isPartOf"]["hydra:search"]["hydra:template"],
+            "https://example.org/car/14/{x}/{y}"
+        );
+        let activity = &written["prov:wasGeneratedBy"];
+        assert_eq!(activity["rdfs:label"], "reduce_profile");
+        assert_eq!(activity["prov:used"]["rdfs:label"], profile.get_source().name.as_str());
+
+        // tags with IRI values, and the IRI-valued concepts of the profile, are coerced to @id
+        let context = &written["@context"];
+        assert_eq!(context["osm:highway"]["@type"], "@id");
+        assert!(context["osm:name"].get("@type").is_none());
+        assert!(context["osm:maxspeed"].get("@type").is_none());
+        for concept in profile.get_used_concepts() {
+            if io::tiles::IRI_VALUED_TERMS.contains(&concept.as_str()) {
+                assert_eq!(context[&concept]["@type"], "@id");
+            }
+        }
+
+        let mut description = TileDescription::new(DEFAULT_BASE_URL, "merge", None);
+        description.context_url = Some("https://example.org/context.jsonld".to_owned());
+        write_derived_tile(tile.clone(), &get_tile_path(root, &coord), &description).unwrap();
+        let file = fs::File::open(get_tile_path(root, &coord)).unwrap();
+        let mut data = String::new();
+        flate2::read::GzDecoder::new(file).read_to_string(&mut data).unwrap();
+        let written: serde_json::Value = serde_json::from_str(&data).unwrap();
+        assert_eq!(written["@context"][0], "https://example.org/context.jsonld");
+        assert_eq!(written["@context"][1]["osm:oneway"]["@type"], "@id");
+
+        // the metadata doesn't end up in the graph
+        let loaded = load_tile(&coord, root).unwrap().0;
+        assert_eq!(loaded.get_nodes().len(), tile.get_nodes().len());
+        assert_eq!(loaded.get_ways().len(), tile.get_ways().len());
+    }
+
+    #[test]
+    fn test_turn_restrictions() {
+        use entities::graph::Graph;
+        use entities::relation::RestrictionKind;
+        use entities::turn_graph::TurnGraph;
+        use std::collections::{BTreeMap, BTreeSet};
+
+        // a crossing with a short eastern arm, left turns from the west and
+        // anything but going straight on from the south are forbidden
+        let node = |id: u32| format!("http://www.openstreetmap.org/node/{}", id);
+        let data = r#"{"@graph": [
+            {"@type": "osm:Node", "@id": "http://www.openstreetmap.org/node/1", "geo:lat": 51.0, "geo:long": 4.0},
+            {"@type": "osm:Node", "@id": "http://www.openstreetmap.org/node/2", "geo:lat": 51.0, "geo:long": 3.99},
+            {"@type": "osm:Node", "@id": "http://www.openstreetmap.org/node/3", "geo:lat": 51.0, "geo:long": 4.001},
+            {"@type": "osm:Node", "@id": "http://www.openstreetmap.org/node/4", "geo:lat": 51.01, "geo:long": 4.0},
+            {"@type": "osm:Node", "@id": "http://www.openstreetmap.org/node/5", "geo:lat": 50.99, "geo:long": 4.0},
+            {"@type": "osm:Way", "@id": "west", "osm:highway": "osm:Primary", "osm:hasNodes": [
+                "http://www.openstreetmap.org/node/2", "http://www.openstreetmap.org/node/1"]},
+            {"@type": "osm:Way", "@id": "east", "osm:highway": "osm:Primary", "osm:hasNodes": [
+                "http://www.openstreetmap.org/node/1", "http://www.openstreetmap.org/node/3"]},
+            {"@type": "osm:Way", "@id": "north", "osm:highway": "osm:Primary", "osm:hasNodes": [
+                "http://www.openstreetmap.org/node/1", "http://www.openstreetmap.org/node/4"]},
+            {"@type": "osm:Way", "@id": "south", "osm:highway": "osm:Primary", "osm:hasNodes": [
+                "http://www.openstreetmap.org/node/1", "http://www.openstreetmap.org/node/5"]},
+            {"@type": "osm:Relation", "@id": "r1", "osm:type": "restriction", "osm:restriction": "no_left_turn",
+             "osm:hasMembers": [{"@id": "west", "role": "from"}, {"@id": "http://www.openstreetmap.org/node/1", "role": "via"},
+                                {"@id": "north", "role": "to"}]},
+            {"@type": "osm:Relation", "@id": "r2", "osm:type": "restriction", "osm:restriction": "only_straight_on",
+             "osm:hasMembers": [{"@id": "south", "role": "from"}, {"@id": "http://www.openstreetmap.org/node/1", "role": "via"},
+                                {"@id": "north", "role": "to"}]}
+        ]}"#;
+        let coord = util::deg2num(51.0, 4.0, 14);
+        let (tile, _) = read_tile(&coord, data.as_bytes(), ParseMode::Strict, &SpeedTable::default()).unwrap();
+        let profile = CompiledProfile::new(load_car_profile().unwrap());
+        let modes = profile.get_profile().get_transport_modes();
+        assert_eq!(modes, vec!["motorcar", "motor_vehicle", "vehicle"]);
+        assert_eq!(tile.get_turn_restrictions(&modes).len(), 2);
+
+        let plain = Graph::new(tile.get_weighted_segments(&profile));
+        let graph = TurnGraph::new(tile.get_weighted_way_segments(&profile), &tile.get_turn_restrictions(&modes));
+
+        // the left turn becomes a detour with a u-turn at the end of the eastern arm
+        let north = node(4);
+        let expected: BTreeSet<String> = vec![node(1), node(2), node(4)].into_iter().collect();
+        assert_eq!(plain.necessary_nodes(&node(2), vec![&north]), expected);
+        let expected: BTreeSet<String> = vec![node(1), node(2), node(3), node(4)].into_iter().collect();
+        assert_eq!(graph.necessary_nodes(&node(2), vec![&north]), expected);
+
+        // going straight on from the south is still allowed, turning right is not
+        let east = node(3);
+        assert_eq!(graph.necessary_nodes(&node(5), vec![&north]).len(), 3);
+        let detour = graph.necessary_nodes(&node(5), vec![&east]);
+        assert!(detour.contains(&node(4)));
+        assert!(!detour.contains(&node(2)));
+
+        let root = std::env::temp_dir().join("preprocess_turns");
+        let root = root.to_str().unwrap();
+        let description = TileDescription::new(DEFAULT_BASE_URL, "test", None);
+        write_derived_tile(tile.clone(), &get_tile_path(root, &coord), &description).unwrap();
+        let binary = create_binary_tile(root, &coord, &profile, &BinaryTileOptions::default()).0;
+        assert_eq!(binary.edges.len(), 8);
+
+        // one left turn, and the right turn, the left turn and the u-turn from the south
+        let turns: Vec<(String, String, String)> = binary
+            .forbidden_turns
+            .iter()
+            .map(|turn| {
+                let (from, to) = (&binary.edges[turn.from], &binary.edges[turn.to]);
+                assert_eq!(from.to, to.from);
+                let id = |label: usize| binary.locations[label].id.clone();
+                (id(from.from), id(from.to), id(to.to))
+            })
+            .collect();
+        assert_eq!(turns.len(), 4);
+        assert!(turns.contains(&(node(2), node(1), node(4))));
+        assert!(turns.contains(&(node(5), node(1), node(5))));
+        assert!(!turns.contains(&(node(5), node(1), node(4))));
+
+        assert_eq!(decode_compact_tile("memory", &encode_compact_tile(&binary)).unwrap(), binary);
+        let encoded = encode_csr_graph(&binary).unwrap();
+        let csr = CsrGraph::new(&encoded).unwrap();
+        assert_eq!(csr.get_forbidden_turns().count(), 4);
+        assert_eq!(csr.to_weighted_tile(binary.header.clone()), binary);
+
+        // plain restrictions only bind vehicles, mode-specific ones only their own mode and its sub-modes
+        let pedestrian = io::profile::load_pedestrian_profile().unwrap();
+        let bicycle = load_bicycle_profile().unwrap();
+        assert!(tile.get_turn_restrictions(&pedestrian.get_transport_modes()).is_empty());
+        assert_eq!(tile.get_turn_restrictions(&bicycle.get_transport_modes()).len(), 2);
+
+        let members = tile.get_relations()["r1"].get_members().to_vec();
+        let relation = |tags: &[(&str, &str)]| {
+            let mut tags: BTreeMap<String, String> = tags.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+            tags.insert("osm:type".to_owned(), "restriction".to_owned());
+            entities::relation::Relation::new("r".to_owned(), members.clone(), tags, vec![])
+        };
+        let car_only = relation(&[("osm:restriction:motorcar", "no_left_turn")]);
+        assert!(car_only.get_turn_restriction(&modes).is_some());
+        assert!(car_only.get_turn_restriction(&pedestrian.get_transport_modes()).is_none());
+        assert!(car_only.get_turn_restriction(&bicycle.get_transport_modes()).is_none());
+        let trucks = relation(&[("osm:restriction", "no_left_turn"), ("osm:restriction:hgv", "only_straight_on")]);
+        assert_eq!(trucks.get_turn_restriction(&["hgv", "motor_vehicle", "vehicle"]).unwrap().kind, RestrictionKind::Mandatory);
+        assert_eq!(trucks.get_turn_restriction(&modes).unwrap().kind, RestrictionKind::Prohibitory);
+        let except = relation(&[("osm:restriction", "no_left_turn"), ("osm:except", "psv; bicycle")]);
+        assert!(except.get_turn_restriction(&modes).is_some());
+        assert!(except.get_turn_restriction(&bicycle.get_transport_modes()).is_none());
+    }
+
+    #[test]
+    fn test_maxspeed() {
+        use util::maxspeed::MaxSpeed;
+
+        let speeds = SpeedTable::default();
+        assert_eq!(speeds.interpret("50"), MaxSpeed::Limit(50.));
+        assert!((speeds.interpret("30 mph").get_limit().unwrap() - 48.28032).abs() < 1e-9);
+        assert!((speeds.interpret("10 knots").get_limit().unwrap() - 18.52).abs() < 1e-9);
+        assert_eq!(speeds.interpret("walk"), MaxSpeed::Limit(6.));
+        assert_eq!(speeds.interpret("none"), MaxSpeed::Unlimited);
+        assert_eq!(speeds.interpret("signals"), MaxSpeed::Variable);
+        assert_eq!(speeds.interpret("BE:urban"), MaxSpeed::Limit(50.));
+        assert_eq!(speeds.interpret("BE-VLG:rural"), MaxSpeed::Limit(70.));
+        assert_eq!(speeds.interpret("BE-WAL:rural"), MaxSpeed::Limit(90.));
+        assert_eq!(speeds.interpret("DE:motorway"), MaxSpeed::Unlimited);
+        assert_eq!(speeds.interpret("50;70"), MaxSpeed::Limit(50.));
+        assert_eq!(speeds.interpret("XX:urban"), MaxSpeed::Invalid);
+        assert_eq!(speeds.interpret("50;fast"), MaxSpeed::Invalid);
+
+        let path = std::env::temp_dir().join("preprocess_speeds.json");
+        fs::write(&path, r#"{"XX:urban": "40", "BE:urban": "30"}"#).unwrap();
+        let custom = SpeedTable::load(path.to_str().unwrap()).unwrap();
+        assert_eq!(custom.interpret("XX:urban"), MaxSpeed::Limit(40.));
+        assert_eq!(custom.interpret("BE:urban"), MaxSpeed::Limit(30.));
+        assert_eq!(custom.interpret("BE:rural"), MaxSpeed::Limit(90.));
+
+        // the profile sees the interpreted limit, the tag keeps the original value
+        let coord = TileCoordinate::new(8345, 5495, 14);
+        let data = r#"{"@graph": [
+            {"@type": "osm:Node", "@id": "n1", "geo:lat": 50.85, "geo:long": 3.31},
+            {"@type": "osm:Node", "@id": "n2", "geo:lat": 50.85, "geo:long": 3.32},
+            {"@type": "osm:Way", "@id": "w1", "osm:hasNodes": ["n1", "n2"], "osm:highway": "osm:Primary", "osm:maxspeed": "XX:urban"},
+            {"@type": "osm:Way", "@id": "w2", "osm:hasNodes": ["n1", "n2"], "osm:maxspeed": "signals"}
+        ]}"#;
+        let (tile, warnings) = read_tile(&coord, data.as_bytes(), ParseMode::Strict, &custom).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(tile.get_ways()["w1"].get_max_speed(), &Some(40.));
+        assert_eq!(tile.get_ways()["w2"].get_max_speed(), &None);
+        assert_eq!(tile.get_ways()["w2"].get_tags()["osm:maxspeed"], "signals");
+        let car = load_car_profile().unwrap();
+        assert_eq!(car.get_speed(&tile.get_ways()["w1"]), 40.);
+
+        // derived tiles keep the original tag along with the limit it was interpreted as,
+        // so the custom table still applies when the tasks load them with the default one
+        let root = std::env::temp_dir().join("preprocess_maxspeed");
+        let root = root.to_str().unwrap();
+        let description = TileDescription::new(DEFAULT_BASE_URL, "test", None);
+        write_derived_tile(tile, &get_tile_path(root, &coord), &description).unwrap();
+        let loaded = load_tile(&coord, root).unwrap().0;
+        assert_eq!(loaded.get_ways()["w1"].get_tags()["osm:maxspeed"], "XX:urban");
+        assert_eq!(loaded.get_ways()["w1"].get_max_speed(), &Some(40.));
+        assert_eq!(car.get_speed(&loaded.get_ways()["w1"]), 40.);
+
+        // reduced tiles drop the tag, but not the limit
+        let reduced_root = std::env::temp_dir().join("preprocess_maxspeed_reduced");
+        let reduced_root = reduced_root.to_str().unwrap();
+        let reduced = create_profile_tile(root, &coord, &CompiledProfile::new(car)).0;
+        write_derived_tile(reduced, &get_tile_path(reduced_root, &coord), &description).unwrap();
+        let loaded = load_tile(&coord, reduced_root).unwrap().0;
+        assert_eq!(loaded.get_ways()["w1"].get_max_speed(), &Some(40.));
+    }
+
+    // cargo test --release bench_tile_parsers -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_tile_parsers() {
+        let coord = TileCoordinate::new(8345, 5495, 14);
+        let iterations = 50;
+        let speeds = SpeedTable::default();
+
+        let start = Instant::now();
+        for _ in 0..iterations {
+            parse_tile(&coord, read_test_tile(&coord), ParseMode::Lenient, &speeds).unwrap();
+        }
+        let tree = start.elapsed() / iterations;
+
+        let start = Instant::now();
+        for _ in 0..iterations {
+            load_tile(&coord, "./test_data").unwrap();
+        }
+        let streaming = start.elapsed() / iterations;
+
+        // the tree parser also keeps the full text and its `Value` tree in memory
+        println!("decompressed size: {} bytes", read_test_tile(&coord).len());
+        println!("tree: {:?} per tile, streaming: {:?} per tile", tree, streaming);
+    }
+
     #[test]
     fn test_profile() {
-        let car_profile = load_car_profile().unwrap();
+        let car_profile = CompiledProfile::new(load_car_profile().unwrap());
         let coord = TileCoordinate::new(8345, 5495, 14);
-        let tile = create_profile_tile("./test_data", &coord, &car_profile);
+        let tile = create_profile_tile("./test_data", &coord, &car_profile).0;
         assert_eq!(tile.get_nodes().len(), 669);
         assert_eq!(tile.get_ways().len(), 157);
 
-        let bike_profile = load_bicycle_profile().unwrap();
+        let bike_profile = CompiledProfile::new(load_bicycle_profile().unwrap());
         let coord = TileCoordinate::new(8345, 5495, 14);
-        let tile = create_profile_tile("./test_data", &coord, &bike_profile);
+        let tile = create_profile_tile("./test_data", &coord, &bike_profile).0;
         assert_eq!(tile.get_nodes().len(), 630);
         assert_eq!(tile.get_ways().len(), 141);
     }
 
+    #[test]
+    fn test_profile_errors() {
+        use io::profile::{find_profile_path, parse_profile, ProfileError};
+
+        assert!(find_profile_path("pedestrian", &["./does_not_exist"]).is_ok());
+        assert!(matches!(
+            find_profile_path("truck", &[]),
+            Err(ProfileError::NotFound(_, _))
+        ));
+        assert!(matches!(
+            load_profile("./does_not_exist.jsonld"),
+            Err(ProfileError::NotAFile(_))
+        ));
+
+        let error = parse_profile("{\n  \"hasAccessRules\": 5\n}").unwrap_err();
+        assert_eq!(error.line(), 2);
+    }
+
+    #[test]
+    fn test_rule_order() {
+        use io::profile::parse_profile;
+
+        // motorcar (order 2) takes precedence over access (order 5) and highway (order 6)
+        let car_profile = load_car_profile().unwrap();
+        assert!(!car_profile.has_access(&tagged_way(&[
+            ("osm:highway", "osm:Residential"),
+            ("osm:motorcar", "osm:NoAccess"),
+        ])));
+        assert!(car_profile.has_access(&tagged_way(&[
+            ("osm:access", "osm:Private"),
+            ("osm:motorcar", "osm:Designated"),
+        ])));
+        assert!(!car_profile.has_access(&tagged_way(&[("osm:highway", "osm:Footway")])));
+
+        // the order decides, not the position in the file
+        // equal orders fall back to the position in the file, unordered rules come last
+        let profile = parse_profile(r#"{
+            "hasAccessRules": [
+                {"concludes": {"hasAccess": true}},
+                {"match": {"hasPredicate": "osm:highway", "hasObject": "osm:Track"}, "concludes": {"hasAccess": true}, "hasOrder": 2},
+                {"match": {"hasPredicate": "osm:access", "hasObject": "osm:NoAccess"}, "concludes": {"hasAccess": false}, "hasOrder": 1},
+                {"match": {"hasPredicate": "osm:highway", "hasObject": "osm:Steps"}, "concludes": {"hasAccess": false}, "hasOrder": 2},
+                {"match": {"hasPredicate": "osm:highway", "hasObject": "osm:Steps"}, "concludes": {"hasAccess": true}, "hasOrder": 2}
+            ],
+            "hasOnewayRules": [],
+            "hasSpeedRules": [],
+            "hasPriorityRules": [],
+            "hasObstacleRules": [],
+            "hasObstacleTimeRules": []
+        }"#).unwrap();
+        assert!(!profile.has_access(&tagged_way(&[
+            ("osm:highway", "osm:Track"),
+            ("osm:access", "osm:NoAccess"),
+        ])));
+        assert!(profile.has_access(&tagged_way(&[("osm:highway", "osm:Track")])));
+        assert!(!profile.has_access(&tagged_way(&[("osm:highway", "osm:Steps")])));
+        assert!(profile.has_access(&tagged_way(&[("osm:highway", "osm:Footway")])));
+    }
+
+    #[test]
+    fn test_compound_conditions() {
+        use io::profile::parse_profile;
+
+        let profile = parse_profile(r#"{
+            "hasAccessRules": [
+                {
+                    "match": {"and": [
+                        {"hasPredicate": "osm:highway", "hasObject": "osm:Track"},
+                        {"hasPredicate": "osm:tracktype", "hasObject": "osm:Grade5"}
+                    ]},
+                    "concludes": {"hasAccess": false},
+                    "hasOrder": 0
+                },
+                {
+                    "match": {"or": [
+                        {"not": {"hasPredicate": "osm:motorcar"}},
+                        {"hasPredicate": "osm:motorcar", "hasObject": "osm:FreeAccess"}
+                    ]},
+                    "concludes": {"hasAccess": true},
+                    "hasOrder": 1
+                },
+                {"concludes": {"hasAccess": false}, "hasOrder": 100}
+            ],
+            "hasOnewayRules": [],
+            "hasSpeedRules": [],
+            "hasPriorityRules": [
+                {"match": {"hasObject": "osm:Cobblestone"}, "concludes": {"hasPriority": 0.5}}
+            ],
+            "hasObstacleRules": [],
+            "hasObstacleTimeRules": []
+        }"#).unwrap();
+
+        assert!(!profile.has_access(&tagged_way(&[
+            ("osm:highway", "osm:Track"),
+            ("osm:tracktype", "osm:Grade5"),
+        ])));
+        assert!(profile.has_access(&tagged_way(&[
+            ("osm:highway", "osm:Track"),
+            ("osm:tracktype", "osm:Grade1"),
+        ])));
+        assert!(profile.has_access(&tagged_way(&[("osm:motorcar", "osm:FreeAccess")])));
+        assert!(!profile.has_access(&tagged_way(&[("osm:motorcar", "osm:Private")])));
+
+        assert_eq!(profile.get_multiplier(&tagged_way(&[("osm:surface", "osm:Cobblestone")])), 2.);
+        assert_eq!(profile.get_multiplier(&tagged_way(&[("osm:surface", "osm:Asphalt")])), 1.);
+
+        let concepts = profile.get_used_concepts();
+        assert!(concepts.contains("osm:tracktype"));
+        assert!(concepts.contains("osm:motorcar"));
+        assert!(concepts.contains("osm:Cobblestone"));
+        assert_eq!(profile.get_open_objects().into_iter().collect::<Vec<_>>(), vec!["osm:Cobblestone"]);
+
+        // object-only rules keep matching on reduced tiles
+        let root = std::env::temp_dir().join("preprocess_open_objects");
+        let root = root.to_str().unwrap();
+        let coord = TileCoordinate::new(8344, 5494, 14);
+        let data = r#"{"@graph": [
+            {"@type": "osm:Node", "@id": "n1", "geo:lat": 50.85, "geo:long": 3.31},
+            {"@type": "osm:Node", "@id": "n2", "geo:lat": 50.85, "geo:long": 3.32},
+            {"@type": "osm:Way", "@id": "w1", "osm:hasNodes": ["n1", "n2"], "osm:surface": "osm:Cobblestone"}
+        ]}"#;
+        let (tile, _) = read_tile(&coord, data.as_bytes(), ParseMode::Strict, &SpeedTable::default()).unwrap();
+        let description = TileDescription::new(DEFAULT_BASE_URL, "test", None);
+        write_derived_tile(tile, &get_tile_path(root, &coord), &description).unwrap();
+        let compiled = CompiledProfile::new(profile);
+        let reduced = create_profile_tile(root, &coord, &compiled).0;
+        assert_eq!(reduced.get_ways()["w1"].get_tags()["osm:surface"], "osm:Cobblestone");
+        assert_eq!(compiled.get_profile().get_multiplier(&reduced.get_ways()["w1"]), 2.);
+
+        // predicate-only rules in the bundled profiles now match as well
+        let bike_profile = load_bicycle_profile().unwrap();
+        assert_eq!(bike_profile.get_multiplier(&tagged_way(&[("osm:cycleway", "osm:Lane")])), 1. / 1.4);
+    }
+
+    #[test]
+    fn test_numeric_conditions() {
+        use io::profile::parse_profile;
+        use util::units::parse_numeric_tag;
+
+        assert_eq!(parse_numeric_tag("osm:maxweight", "7.5"), Some(7.5));
+        assert_eq!(parse_numeric_tag("osm:maxweight", "3500 kg"), Some(3.5));
+        assert_eq!(parse_numeric_tag("osm:maxwidth", "2,5 m"), Some(2.5));
+        assert!((parse_numeric_tag("osm:maxheight", "12'6\"").unwrap() - 3.81).abs() < 1e-9);
+        assert_eq!(parse_numeric_tag("osm:incline", "-6%"), Some(-6.));
+        assert!((parse_numeric_tag("osm:incline", "45°").unwrap() - 100.).abs() < 1e-9);
+        assert_eq!(parse_numeric_tag("osm:incline", "up"), None);
+        assert!((parse_numeric_tag("osm:maxspeed", "30 mph").unwrap() - 48.28032).abs() < 1e-9);
+
+        let profile = parse_profile(r#"{
+            "hasAccessRules": [
+                {"match": {"hasPredicate": "osm:maxweight", "lessThan": 7.5}, "concludes": {"hasAccess": false}, "hasOrder": 0},
+                {"match": {"hasPredicate": "osm:maxwidth", "lessThanOrEqual": 2.5}, "concludes": {"hasAccess": false}, "hasOrder": 0},
+                {"concludes": {"hasAccess": true}, "hasOrder": 100}
+            ],
+            "hasOnewayRules": [],
+            "hasSpeedRules": [],
+            "hasPriorityRules": [
+                {"match": {"or": [
+                    {"hasPredicate": "osm:incline", "greaterThan": 6},
+                    {"hasPredicate": "osm:incline", "lessThan": -6}
+                ]}, "concludes": {"hasPriority": 0.25}, "hasOrder": 0}
+            ],
+            "hasObstacleRules": [],
+            "hasObstacleTimeRules": []
+        }"#).unwrap();
+
+        assert!(!profile.has_access(&tagged_way(&[("osm:maxweight", "3.5")])));
+        assert!(!profile.has_access(&tagged_way(&[("osm:maxweight", "5000 kg")])));
+        assert!(profile.has_access(&tagged_way(&[("osm:maxweight", "12")])));
+        assert!(!profile.has_access(&tagged_way(&[("osm:maxwidth", "2.5")])));
+        assert!(profile.has_access(&tagged_way(&[("osm:maxwidth", "default")])));
+        assert!(profile.has_access(&tagged_way(&[])));
+
+        assert_eq!(profile.get_multiplier(&tagged_way(&[("osm:incline", "10%")])), 4.);
+        assert_eq!(profile.get_multiplier(&tagged_way(&[("osm:incline", "-8%")])), 4.);
+        assert_eq!(profile.get_multiplier(&tagged_way(&[("osm:incline", "3%")])), 1.);
+        assert!(profile.get_open_predicates().contains("osm:incline"));
+    }
+
+    #[test]
+    fn test_one_way_direction() {
+        use entities::node::Node;
+        use entities::profile::Direction;
+        use entities::tile::Tile;
+        use entities::way::Way;
+        use std::collections::BTreeMap;
+
+        let profile = CompiledProfile::new(load_car_profile().unwrap());
+        let tile_with_oneway = |oneway: &str| {
+            let mut nodes = BTreeMap::new();
+            for (id, long) in [("a", 4.40), ("b", 4.41)].iter() {
+                let node = Node::new(id.to_string(), 51.2, *long, BTreeMap::new(), vec![]);
+                nodes.insert(id.to_string(), node);
+            }
+
+            let mut tags = BTreeMap::new();
+            tags.insert("osm:highway".to_owned(), "osm:Residential".to_owned());
+            tags.insert("osm:oneway".to_owned(), oneway.to_owned());
+            let way = Way::new("w".to_owned(), vec!["a".to_owned(), "b".to_owned()], None, None, tags, vec![]);
+            assert!(profile.has_access(&way));
+
+            let direction = profile.evaluate_way(&way).direction;
+            let mut ways = BTreeMap::new();
+            ways.insert("w".to_owned(), way);
+            (direction, Tile::new(TileCoordinate::new(0, 0, 14), nodes, ways, BTreeMap::new()))
+        };
+
+        let (direction, tile) = tile_with_oneway("osm:InOrder");
+        assert_eq!(direction, Direction::Forward);
+        let segments = tile.get_weighted_segments(&profile);
+        assert_eq!(segments.len(), 1);
+        assert_eq!((segments[0].segment.from, segments[0].segment.to), ("a", "b"));
+
+        let (direction, tile) = tile_with_oneway("osm:InReverseOrder");
+        assert_eq!(direction, Direction::Backward);
+        let segments = tile.get_weighted_segments(&profile);
+        assert_eq!(segments.len(), 1);
+        assert_eq!((segments[0].segment.from, segments[0].segment.to), ("b", "a"));
+
+        let (direction, tile) = tile_with_oneway("osm:Bidirectional");
+        assert_eq!(direction, Direction::Both);
+        assert_eq!(tile.get_weighted_segments(&profile).len(), 2);
+    }
+
+    #[test]
+    fn test_validate_profile() {
+        for name in ["car", "bicycle", "pedestrian"].iter() {
+            let path = io::profile::find_profile_path(name, &[]).unwrap();
+            let issues = validate_profile(&fs::read_to_string(path).unwrap());
+            assert!(issues.iter().all(|issue| !issue.is_error()), "{:?}", issues);
+        }
+
+        let issues = validate_profile(r#"{
+            "@context": {"osm": "https://w3id.org/openstreetmap/terms#"},
+            "hasAccessRules": [
+                {"concludes": {"hasAccess": true}, "hasOrder": 0},
+                {
+                    "match": {"hasPredicate": "osm:highway", "hasObject": "osm:Steps"},
+                    "concludes": {"hasSpeed": 5},
+                    "hasOrder": 1
+                }
+            ],
+            "hasOnewayRules": [],
+            "hasSpeedRules": [
+                {"match": {"hasPredicate": "osm:highway"}, "concludes": {"hasSpeed": 30}, "hasOrder": 0},
+                {"match": {"hasPredicate": "osm:highway", "hasObject": "osm:Primary"}, "concludes": {"hasSpeed": 50}, "hasOrder": 1},
+                {"match": {"hasPredicate": "osm:name", "hasObject": "foo:Bar"}, "concludes": {"hasSpeed": 50}, "hasOrder": 2}
+            ],
+            "hasPriorityRules": [],
+            "hasObstacleRules": []
+        }"#);
+
+        let errors: Vec<_> = issues.iter().filter(|issue| issue.is_error()).collect();
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().any(|e| e.line == Some(5) && e.message.contains("lacks hasAccess")));
+        assert!(errors.iter().any(|e| e.message.contains("missing hasObstacleTimeRules")));
+
+        let warnings: Vec<_> = issues.iter().filter(|issue| !issue.is_error()).collect();
+        assert!(warnings.iter().any(|w| w.line == Some(5) && w.message.contains("rule on line 4")));
+        assert!(warnings.iter().any(|w| w.line == Some(5) && w.message.contains("hasSpeed is ignored")));
+        assert!(warnings.iter().any(|w| w.line == Some(14) && w.message.contains("unreachable")));
+        assert!(warnings.iter().any(|w| w.line == Some(15) && w.message.contains("prefix foo")));
+        assert!(warnings.iter().any(|w| w.line == Some(15) && w.message.contains("not an IRI")));
+    }
+
+    #[test]
+    fn test_compiled_profile() {
+        use io::profile::load_pedestrian_profile;
+
+        let profiles = vec![
+            load_car_profile().unwrap(),
+            load_bicycle_profile().unwrap(),
+            load_pedestrian_profile().unwrap(),
+        ];
+
+        for profile in profiles {
+            let compiled = CompiledProfile::new(profile);
+            let profile = compiled.get_profile();
+
+            for x in 8344..=8348 {
+                for y in 5494..=5496 {
+                    let coord = TileCoordinate::new(x, y, 14);
+                    let tile = load_tile(&coord, "./test_data").unwrap().0;
+
+                    for node in tile.get_nodes().values() {
+                        let node_profile = compiled.evaluate_node(node);
+                        assert_eq!(node_profile.obstacle, profile.is_obstacle(node));
+                        assert_eq!(node_profile.obstacle_time, profile.get_obstacle_time(node));
+                    }
+
+                    for way in tile.get_ways().values() {
+                        let way_profile = compiled.evaluate_way(way);
+                        assert_eq!(way_profile.access, profile.has_access(way));
+                        assert_eq!(way_profile.direction, profile.get_direction(way));
+                        assert_eq!(way_profile.speed, profile.get_speed(way));
+                        assert_eq!(way_profile.multiplier, profile.get_multiplier(way));
+
+                        for segment in way.get_segments() {
+                            let from = &tile.get_nodes()[segment.from];
+                            let to = &tile.get_nodes()[segment.to];
+                            let to_profile = compiled.evaluate_node(to);
+                            assert_eq!(
+                                compiled.get_cost(from, to, &way_profile, &to_profile),
+                                profile.get_cost(from, to, way)
+                            );
+                            assert_eq!(
+                                compiled.get_duration(from, to, &way_profile),
+                                profile.get_duration(from, to, way)
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let profile = CompiledProfile::new(load_car_profile().unwrap());
+        let coord = TileCoordinate::new(8345, 5495, 14);
+        let options = BinaryTileOptions::default();
+        let tile = create_binary_tile("./test_data", &coord, &profile, &options).0;
+        assert_eq!(tile.locations.len(), 725);
+        assert!(!tile.edges.is_empty());
+
+        let expected = create_binary_tile("./test_data", &coord, &profile, &options).0;
+        let path = std::env::temp_dir().join("preprocess_round_trip.bin");
+        let path = path.to_str().unwrap();
+        write_flexbuffers_tile(tile, path);
+        let loaded = load_binary_tile(path).unwrap();
+        assert_eq!(loaded, expected);
+
+        assert_eq!(loaded.header.version, entities::weighted_tile::SCHEMA_VERSION);
+        assert_eq!(loaded.header.coordinate, coord);
+        assert_eq!(loaded.header.profile_name, "car");
+        assert_eq!(loaded.header.profile_hash, profile.get_profile().get_source().hash);
+
+        let summary = describe_binary_tile(&loaded, 2);
+        assert!(summary.contains("nodes: 725"));
+        assert!(summary.contains("profile: car"));
+        assert_eq!(summary.lines().count(), 13);
+
+        // files of a different schema version are refused
+        let mut outdated = create_binary_tile("./test_data", &coord, &profile, &options).0;
+        outdated.header.version += 1;
+        write_flexbuffers_tile(outdated, path);
+        assert!(matches!(load_binary_tile(path), Err(io::tiles::TileError::IncompatibleBinary(_, _))));
+
+        assert!(load_binary_tile("./does_not_exist.bin").is_err());
+        assert!(load_binary_tile("./car.jsonld").is_err());
+    }
+
+    #[test]
+    fn test_compact_binary() {
+        let profile = CompiledProfile::new(load_car_profile().unwrap());
+        let coord = TileCoordinate::new(8345, 5495, 14);
+        let options = BinaryTileOptions::default();
+        let tile = create_binary_tile("./test_data", &coord, &profile, &options).0;
+
+        let encoded = encode_compact_tile(&tile);
+        assert_eq!(decode_compact_tile("memory", &encoded).unwrap(), tile);
+
+        // non-numeric ids and custom labels take the slow path
+        let mut odd = create_binary_tile("./test_data", &coord, &profile, &options).0;
+        odd.locations[0].id = "_:b0".to_owned();
+        odd.labels.insert("extra".to_owned(), 3);
+        assert_eq!(decode_compact_tile("memory", &encode_compact_tile(&odd)).unwrap(), odd);
+
+        // truncated or corrupted data is rejected instead of panicking
+        assert!(decode_compact_tile("memory", &encoded[..encoded.len() / 2]).is_err());
+        let mut corrupted = encoded.clone();
+        corrupted[COMPACT_MAGIC.len()] += 1;
+        assert!(matches!(
+            decode_compact_tile("memory", &corrupted),
+            Err(io::tiles::TileError::IncompatibleBinary(_, _))
+        ));
+
+        let flex_path = std::env::temp_dir().join("preprocess_size.bin");
+        let flex_path = flex_path.to_str().unwrap();
+        write_flexbuffers_tile(create_binary_tile("./test_data", &coord, &profile, &options).0, flex_path);
+        let compact_path = std::env::temp_dir().join("preprocess_size_compact.bin");
+        let compact_path = compact_path.to_str().unwrap();
+        write_compact_tile(tile, compact_path);
+        assert_eq!(load_binary_tile(compact_path).unwrap().locations.len(), 725);
+
+        let json_size = fs::metadata("./test_data/14/8345/5495.jsonld.zip").unwrap().len();
+        let flex_size = fs::metadata(flex_path).unwrap().len();
+        let compact_size = fs::metadata(compact_path).unwrap().len();
+        assert!(compact_size < json_size);
+        assert!(compact_size * 4 < flex_size);
+    }
+
+    #[test]
+    fn test_csr_graph() {
+        let profile = CompiledProfile::new(load_car_profile().unwrap());
+        let coord = TileCoordinate::new(8345, 5495, 14);
+        let options = BinaryTileOptions::default();
+        let tile = create_binary_tile("./test_data", &coord, &profile, &options).0;
+
+        let encoded = encode_csr_graph(&tile).unwrap();
+        let graph = CsrGraph::new(&encoded).unwrap();
+        assert_eq!(graph.get_node_count(), 725);
+        assert_eq!(graph.get_edge_count(), tile.edges.len());
+
+        for (node, location) in tile.locations.iter().enumerate() {
+            let [lat, lon] = graph.get_location(node);
+            assert_eq!((lat, lon), (location.lat, location.lon));
+
+            let expected: Vec<(usize, u64)> = tile
+                .edges
+                .iter()
+                .filter(|edge| edge.from == node)
+                .map(|edge| (edge.to, edge.weight))
+                .collect();
+            assert_eq!(graph.get_neighbours(node).collect::<Vec<_>>(), expected);
+        }
+        assert_eq!(graph.to_weighted_tile(tile.header.clone()), tile);
+
+        let path = std::env::temp_dir().join("preprocess_csr.bin");
+        let path = path.to_str().unwrap();
+        write_csr_tile(create_binary_tile("./test_data", &coord, &profile, &options).0, path).unwrap();
+        assert_eq!(load_binary_tile(path).unwrap(), tile);
+
+        assert!(CsrGraph::new(&encoded[..encoded.len() / 2]).is_err());
+        let mut odd = create_binary_tile("./test_data", &coord, &profile, &options).0;
+        odd.locations[3].id = "_:b0".to_owned();
+        assert!(matches!(encode_csr_graph(&odd), Err(CsrError::NonNumericId(ref id)) if id == "_:b0"));
+    }
+
+    #[test]
+    fn test_way_references() {
+        let profile = CompiledProfile::new(load_car_profile().unwrap());
+        let coord = TileCoordinate::new(8345, 5495, 14);
+        let options = BinaryTileOptions { way_references: true, ..Default::default() };
+        let tile = create_binary_tile("./test_data", &coord, &profile, &options).0;
+
+        let base = load_tile(&coord, "./test_data").unwrap().0;
+        let references = tile.way_references.as_ref().unwrap();
+        assert_eq!(references.edges.len(), tile.edges.len());
+        for (index, edge) in tile.edges.iter().enumerate() {
+            let way = references.get_way(index).unwrap();
+            assert!(way.length > 0.);
+            assert!(way.tags.contains_key("osm:highway"));
+            assert!(!way.tags.contains_key("osm:hasNodes"));
+
+            let nodes = base.get_ways()[&way.id].get_nodes();
+            assert!(nodes.contains(&tile.locations[edge.from].id));
+            assert!(nodes.contains(&tile.locations[edge.to].id));
+        }
+
+        let decoded = decode_compact_tile("memory", &encode_compact_tile(&tile)).unwrap();
+        assert_eq!(decoded, tile);
+
+        let path = std::env::temp_dir().join("preprocess_way_references.bin");
+        let path = path.to_str().unwrap();
+        write_flexbuffers_tile(create_binary_tile("./test_data", &coord, &profile, &options).0, path);
+        let loaded = load_binary_tile(path).unwrap();
+        assert_eq!(loaded, tile);
+
+        let summary = describe_binary_tile(&loaded, 1);
+        assert!(summary.contains("way_references: true"));
+        assert!(summary.contains(" via http://www.openstreetmap.org/way/"));
+
+        // the csr format doesn't store them
+        write_csr_tile(tile, path).unwrap();
+        let loaded = load_binary_tile(path).unwrap();
+        assert_eq!(loaded.way_references, None);
+        assert!(!loaded.header.parameters.contains_key("way_references"));
+    }
+
+    #[test]
+    fn test_boundary_nodes() {
+        let profile = CompiledProfile::new(load_car_profile().unwrap());
+        let coord = TileCoordinate::new(8345, 5495, 14);
+        let tile = create_binary_tile("./test_data", &coord, &profile, &BinaryTileOptions::default()).0;
+        assert_eq!(tile.boundary_nodes.len(), 46);
+
+        // every boundary node can be found in the tile it's attributed to
+        for boundary in tile.boundary_nodes.iter() {
+            assert_ne!(boundary.tile, coord);
+            let neighbour = load_tile(&boundary.tile, "./test_data").unwrap().0;
+            assert!(neighbour.get_nodes().contains_key(&tile.locations[boundary.node].id));
+        }
+
+        let [e, n, w, s] = util::get_tile_edges(&coord);
+        for (node, location) in tile.locations.iter().enumerate() {
+            let inside = s <= location.lat && location.lat <= n && w <= location.lon && location.lon <= e;
+            assert_eq!(tile.get_boundary_tile(node).is_none(), inside);
+        }
+
+        let leaving = tile.edges.iter().filter(|edge| tile.get_boundary_tile(edge.to).is_some()).count();
+        let summary = describe_binary_tile(&tile, tile.edges.len());
+        assert!(leaving > 0);
+        assert_eq!(summary.matches(", continues in 14/").count(), leaving);
+
+        let decoded = decode_compact_tile("memory", &encode_compact_tile(&tile)).unwrap();
+        assert_eq!(decoded.boundary_nodes, tile.boundary_nodes);
+
+        let encoded = encode_csr_graph(&tile).unwrap();
+        let graph = CsrGraph::new(&encoded).unwrap();
+        assert_eq!(graph.to_weighted_tile(tile.header.clone()), tile);
+    }
+
+    #[test]
+    fn test_metrics() {
+        let profile = CompiledProfile::new(load_car_profile().unwrap());
+        let coord = TileCoordinate::new(8345, 5495, 14);
+        let default = create_binary_tile("./test_data", &coord, &profile, &BinaryTileOptions::default()).0;
+        let options = BinaryTileOptions {
+            way_references: false,
+            metrics: vec![Metric::Duration, Metric::Distance, Metric::Cost],
+        };
+        let tile = create_binary_tile("./test_data", &coord, &profile, &options).0;
+        assert_eq!(tile.secondary_weights.len(), 2);
+
+        for (index, edge) in default.edges.iter().enumerate() {
+            assert_eq!(tile.get_weight(index, Metric::Cost), Some(edge.weight));
+
+            // no tile edge is longer than a few kilometres
+            let distance = tile.get_weight(index, Metric::Distance).unwrap();
+            assert!(distance < 5000);
+        }
+        assert_eq!(default.get_weight(0, Metric::Distance), None);
+
+        assert_eq!(parse_metrics("distance,cost".split(',')), Ok(vec![Metric::Distance, Metric::Cost]));
+        assert!(parse_metrics("cost,distance,cost".split(',')).is_err());
+        assert!(parse_metrics("cost,speed".split(',')).is_err());
+
+        let decoded = decode_compact_tile("memory", &encode_compact_tile(&tile)).unwrap();
+        assert_eq!(decoded, tile);
+
+        let encoded = encode_csr_graph(&tile).unwrap();
+        let graph = CsrGraph::new(&encoded).unwrap();
+        assert_eq!(graph.get_metric_count(), 3);
+        assert_eq!(graph.to_weighted_tile(tile.header.clone()), tile);
+        let distances: Vec<u64> = graph.get_metric_neighbours(0, 1).map(|(_, weight)| weight).collect();
+        let degree = graph.get_neighbours(0).len();
+        assert_eq!(distances, tile.secondary_weights[0][..degree].to_vec());
+
+        let summary = describe_binary_tile(&tile, 1);
+        assert!(summary.contains("metrics: duration (ms), distance (m), cost (ms)"));
+        let sample = summary.lines().last().unwrap();
+        let weights = tile.secondary_weights.iter().map(|weights| weights[0]).collect::<Vec<_>>();
+        let weights = format!("{} ms, {} m, {} ms", tile.edges[0].weight, weights[0], weights[1]);
+        assert!(sample.contains(&weights));
+    }
+
     #[test]
     fn test_transit() {
-        let profile = load_car_profile().unwrap();
+        let profile = CompiledProfile::new(load_car_profile().unwrap());
         let coord = TileCoordinate::new(8345, 5495, 14);
-        let tile = create_transit_tile("./test_data", &coord, &profile);
+        let tile = create_transit_tile("./test_data", &coord, &profile).0;
         assert_eq!(tile.get_nodes().len(), 470);
         assert_eq!(tile.get_ways().len(), 109);
     }
 
+    #[test]
+    fn test_export_geojson() {
+        use tasks::export_geojson::create_feature_collection;
+        use util::edge_nodes::get_edge_nodes;
+
+        let profile = CompiledProfile::new(load_car_profile().unwrap());
+        let coord = TileCoordinate::new(8345, 5495, 14);
+        let tile = load_tile(&coord, "./test_data").unwrap().0;
+        let options = GeoJsonOptions {
+            edge_nodes: true,
+            necessary_nodes: Some(&profile),
+        };
+        let collection = create_feature_collection(&tile, &options);
+        let features = collection["features"].as_array().unwrap();
+        let count = |key: &str, value: serde_json::Value| {
+            features.iter().filter(|feature| feature["properties"][key] == value).count()
+        };
+
+        let lines = features.iter().filter(|f| f["geometry"]["type"] == "LineString").count();
+        assert_eq!(lines, tile.get_ways().len());
+        assert_eq!(count("edge", serde_json::json!(true)), get_edge_nodes(&tile, util::get_tile_edges(&coord)).len());
+
+        // the same ways as the transit tile
+        let transit = create_transit_tile("./test_data", &coord, &profile).0;
+        assert_eq!(count("retained", serde_json::json!(true)), transit.get_ways().len());
+        assert_eq!(count("retained", serde_json::json!(false)), lines - transit.get_ways().len());
+
+        let root = std::env::temp_dir().join("preprocess_geojson");
+        let path = get_geojson_tile_path(root.to_str().unwrap(), &coord);
+        export_geojson("./test_data", &coord, &path, &GeoJsonOptions::default()).unwrap();
+        let written: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written["type"], "FeatureCollection");
+        assert!(written["features"].as_array().unwrap().iter().all(|f| f["properties"].get("edge").is_none()));
+    }
+
+    #[test]
+    fn test_import_extract() {
+        use tasks::import_extract::cut_extract;
+
+        let speeds = SpeedTable::default();
+        let left = TileCoordinate::new(8345, 5495, 14);
+        let right = TileCoordinate::new(8346, 5495, 14);
+        let cut = |path: &str, coords: &[TileCoordinate]| {
+            let mut tiles = Vec::new();
+            cut_extract(path, coords, &speeds, |batch, _| tiles.extend(batch)).map(|_| tiles)
+        };
+        let tiles = cut("./test_data/extract/tiny.osm", &[left, right]).unwrap();
+        assert_eq!(tiles.len(), 2);
+        let (left_tile, right_tile) = (&tiles[0], &tiles[1]);
+        assert_eq!(left_tile.get_coordinate(), &left);
+        let node = |id: u32| format!("http://www.openstreetmap.org/node/{}", id);
+        let way = |id: u32| format!("http://www.openstreetmap.org/way/{}", id);
+
+        // the way that crosses the tile border is part of both tiles, with all of its nodes
+        let crossing = &right_tile.get_ways()[&way(10)];
+        assert_eq!(crossing.get_nodes(), &[node(1), node(2), node(3)][..]);
+        assert_eq!(left_tile.get_ways()[&way(10)], *crossing);
+        assert_eq!(right_tile.get_nodes().len(), 3);
+        assert_eq!(left_tile.get_ways().len(), 2);
+        assert_eq!(left_tile.get_nodes().len(), 4);
+
+        assert_eq!(crossing.get_tags()["osm:highway"], "osm:Residential");
+        assert_eq!(crossing.get_tags()["osm:oneway"], "osm:InOrder");
+        assert_eq!(crossing.get_tags()["osm:name"], "Kerk & Markt");
+        assert_eq!(crossing.get_undefined_tags(), &["lanes=2".to_owned()][..]);
+        assert_eq!(crossing.get_max_speed(), &Some(50.));
+        assert_eq!(left_tile.get_nodes()[&node(1)].get_tags()["osm:highway"], "osm:TrafficSignals");
+
+        // buildings and multipolygons aren't routable, turn restrictions are
+        for tile in &tiles {
+            assert!(!tile.get_ways().contains_key(&way(12)));
+            assert_eq!(tile.get_relations().len(), 1);
+            assert_eq!(tile.get_turn_restrictions(&["vehicle"]).len(), 1);
+        }
+        let restriction = left_tile.get_relations().values().next().unwrap();
+        assert_eq!(restriction.get_tags()["osm:restriction"], "no_left_turn");
+        assert!(restriction.get_undefined_tags().is_empty());
+
+        // both formats describe the same data, up to the precision of the coordinates
+        let pbf_tiles = cut("./test_data/extract/tiny.osm.pbf", &[left, right]).unwrap();
+        for (tile, pbf_tile) in tiles.iter().zip(pbf_tiles.iter()) {
+            assert_eq!(tile.get_ways(), pbf_tile.get_ways());
+            assert_eq!(tile.get_relations(), pbf_tile.get_relations());
+            assert_eq!(tile.get_nodes().len(), pbf_tile.get_nodes().len());
+            for (id, node) in tile.get_nodes() {
+                let pbf_node = &pbf_tile.get_nodes()[id];
+                assert!((node.get_lat() - pbf_node.get_lat()).abs() < 1e-9);
+                assert!((node.get_long() - pbf_node.get_long()).abs() < 1e-9);
+                assert_eq!(node.get_tags(), pbf_node.get_tags());
+            }
+        }
+
+        // the written tiles can be processed like fetched ones
+        let root = std::env::temp_dir().join("preprocess_import");
+        let root = root.to_str().unwrap();
+        let description = TileDescription::new(DEFAULT_BASE_URL, "import_extract", None);
+        write_derived_tile(right_tile.clone(), &get_tile_path(root, &right), &description).unwrap();
+        let loaded = load_tile(&right, root).unwrap().0;
+        assert_eq!(loaded.get_ways().len(), 1);
+        assert_eq!(loaded.get_nodes().len(), 3);
+        assert_eq!(loaded.get_turn_restrictions(&["vehicle"]).len(), 1);
+
+        assert!(cut("./test_data/extract/tiny.json", &[left]).is_err());
+    }
+
     #[test]
     fn test_padded_transit() {
-        let profile = load_car_profile().unwrap();
+        let profile = CompiledProfile::new(load_car_profile().unwrap());
         let coord = TileCoordinate::new(8345, 5495, 14);
-        let tile = create_indirect_transit_tile("./test_data/", 14, &coord, &profile);
+        let tile = create_indirect_transit_tile("./test_data/", 14, &coord, &profile).0;
         assert_eq!(tile.get_nodes().len(), 307);
         assert_eq!(tile.get_ways().len(), 82);
     }
+
+    #[test]
+    fn test_polygon_area() {
+        use entities::area::Polygon;
+
+        let bbox = Areas::Dummy.get_bounding_box();
+        let rectangle = get_tile_coords(&Area::BoundingBox(bbox), 14);
+
+        // lower left half of the dummy area
+        let triangle = Polygon::new(
+            vec![[4.40, 51.15], [4.5, 51.15], [4.40, 51.25], [4.40, 51.15]],
+            vec![],
+        );
+        let tiles = get_tile_coords(&Area::Polygons(vec![triangle]), 14);
+
+        assert!(tiles.len() < rectangle.len());
+        assert!(tiles.iter().all(|coord| rectangle.contains(coord)));
+        assert!(tiles.contains(&util::deg2num(51.16, 4.41, 14)));
+        assert!(!tiles.contains(&util::deg2num(51.24, 4.49, 14)));
+
+        let path = std::env::temp_dir().join("preprocess_area.geojson");
+        let path = path.to_str().unwrap();
+        fs::write(path, r#"{"type": "Polygon", "coordinates": [[[4.40, 51.15], [4.5, 51.15], [4.40, 51.25]]]}"#).unwrap();
+        assert!(load_area_file(path).is_ok());
+        fs::write(path, r#"{"type": "Polygon", "coordinates": [[[4.40, 51.15], [4.5, 51.15], [4.40, 89.0]]]}"#).unwrap();
+        let error = load_area_file(path).unwrap_err();
+        assert_eq!(error.to_string(), "Position 4.4,89 lies outside of the web mercator projection");
+    }
 }
\ No newline at end of file
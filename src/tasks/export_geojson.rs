@@ -0,0 +1,128 @@
+use crate::entities::compiled_profile::CompiledProfile;
+use crate::entities::node::Node;
+use crate::entities::property::Property;
+use crate::entities::tile::Tile;
+use crate::entities::tile_coord::TileCoordinate;
+use crate::io::tiles::{load_tile, TileError};
+use crate::tasks::reduce_transit::get_necessary_nodes;
+use crate::util::edge_nodes::get_edge_nodes;
+use crate::util::get_tile_edges;
+use anyhow::Result;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+
+// simplestyle colours, picked up by geojson.io and most other viewers
+const EDGE_COLOR: &str = "#e41a1c";
+const NECESSARY_COLOR: &str = "#377eb8";
+const DROPPED_COLOR: &str = "#999999";
+
+#[derive(Default)]
+pub struct GeoJsonOptions<'a> {
+    pub edge_nodes: bool,
+    pub necessary_nodes: Option<&'a CompiledProfile>, // the profile to find them with
+}
+
+fn get_position(node: &Node) -> Value {
+    json!([node.get_long(), node.get_lat()])
+}
+
+fn get_properties(
+    tags: &BTreeMap<String, String>,
+    undefined_tags: &[String],
+    properties: &BTreeMap<String, Property>,
+) -> Map<String, Value> {
+    let mut result = Map::new();
+    for (key, value) in tags {
+        result.insert(key.to_string(), json!(value));
+    }
+    for (key, property) in properties {
+        result.insert(key.to_string(), property.to_json());
+    }
+    if !undefined_tags.is_empty() {
+        result.insert("osm:hasTag".to_owned(), json!(undefined_tags));
+    }
+    result
+}
+
+// ways become LineStrings, tagged nodes become Points, as do the highlighted ones;
+// with the necessary nodes, ways are marked as retained or dropped the way `reduce_transit` would
+pub fn create_feature_collection(tile: &Tile, options: &GeoJsonOptions) -> Value {
+    let coord = tile.get_coordinate();
+    let edge_nodes = if options.edge_nodes {
+        get_edge_nodes(tile, get_tile_edges(coord))
+    } else {
+        BTreeSet::new()
+    };
+    let necessary_nodes = match options.necessary_nodes {
+        Some(profile) => get_necessary_nodes(tile, coord, profile),
+        None => BTreeSet::new(),
+    };
+
+    let mut features = Vec::new();
+    for way in tile.get_ways().values() {
+        let coordinates: Vec<Value> = way
+            .get_nodes()
+            .iter()
+            .filter_map(|id| tile.get_nodes().get(id))
+            .map(get_position)
+            .collect();
+        if coordinates.len() < 2 {
+            continue;
+        }
+
+        let mut properties = get_properties(way.get_tags(), way.get_undefined_tags(), way.get_properties());
+        if options.necessary_nodes.is_some() {
+            let retained = way.get_nodes().iter().filter(|id| necessary_nodes.contains(*id)).count() > 1;
+            properties.insert("retained".to_owned(), json!(retained));
+            if !retained {
+                properties.insert("stroke".to_owned(), json!(DROPPED_COLOR));
+            }
+        }
+
+        features.push(json!({
+            "type": "Feature",
+            "id": way.get_id(),
+            "geometry": {"type": "LineString", "coordinates": coordinates},
+            "properties": properties,
+        }));
+    }
+
+    for node in tile.get_nodes().values() {
+        let edge = edge_nodes.contains(node.get_id());
+        let necessary = necessary_nodes.contains(node.get_id());
+        let mut properties = get_properties(node.get_tags(), node.get_undefined_tags(), node.get_properties());
+        if properties.is_empty() && !edge && !necessary {
+            continue;
+        }
+
+        if necessary {
+            properties.insert("necessary".to_owned(), json!(true));
+            properties.insert("marker-color".to_owned(), json!(NECESSARY_COLOR));
+        }
+        if edge {
+            properties.insert("edge".to_owned(), json!(true));
+            properties.insert("marker-color".to_owned(), json!(EDGE_COLOR));
+        }
+
+        features.push(json!({
+            "type": "Feature",
+            "id": node.get_id(),
+            "geometry": {"type": "Point", "coordinates": get_position(node)},
+            "properties": properties,
+        }));
+    }
+
+    json!({"type": "FeatureCollection", "features": features})
+}
+
+pub fn export_geojson(
+    root_dir: &str,
+    coord: &TileCoordinate,
+    path: &str,
+    options: &GeoJsonOptions,
+) -> Result<Vec<TileError>> {
+    let (tile, warnings) = load_tile(coord, root_dir)?;
+    fs::write(path, create_feature_collection(&tile, options).to_string())?;
+    Ok(warnings)
+}
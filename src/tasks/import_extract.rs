@@ -0,0 +1,273 @@
+use crate::entities::node::Node;
+use crate::entities::relation::{Member, Relation};
+use crate::entities::tile::Tile;
+use crate::entities::tile_coord::TileCoordinate;
+use crate::entities::way::Way;
+use crate::io::extract::{read_extract, ExtractError, MemberType, OsmElement, OsmNode, OsmRelation, OsmWay};
+use crate::io::tiles::IRI_VALUED_TERMS;
+use crate::util::deg2num;
+use crate::util::maxspeed::SpeedTable;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+// tags that keep their value as a literal, all other tags without a concept end up in `osm:hasTag`,
+// the restriction tags are what turn restrictions get interpreted from
+const LITERAL_KEYS: &[&str] = &["name", "maxspeed", "type", "restriction", "except"];
+
+const ACCESS_KEYS: &[&str] = &["access", "bicycle", "motor_vehicle", "motorcar", "vehicle"];
+
+// values of the IRI-valued tags that have a concept in the routable tiles vocabulary, named after
+// the camel cased value and an optional suffix, e.g. `very_bad` becomes `osm:VeryBadSmoothness`
+const VOCABULARY: &[(&str, &str, &[&str])] = &[
+    ("highway", "", &[
+        "motorway", "motorway_link", "trunk", "trunk_link", "primary", "primary_link", "secondary",
+        "secondary_link", "tertiary", "tertiary_link", "unclassified", "residential", "living_street",
+        "service", "track", "path", "steps", "road", "bridleway", "pedestrian", "proposed", "give_way",
+        "stop", "traffic_signals",
+    ]),
+    ("access", "", &[
+        "private", "customers", "designated", "destination", "permissive", "delivery", "agricultural",
+        "forestry", "official", "use_sidepath",
+    ]),
+    ("barrier", "", &[
+        "block", "bollard", "bus_trap", "chain", "cycle_barrier", "debris", "entrance", "gate", "lift_gate",
+        "swing_gate", "toll_booth", "turnstile",
+    ]),
+    ("crossing", "", &["uncontrolled", "unmarked"]),
+    ("cycleway", "", &["lane", "track", "opposite_lane", "opposite_track"]),
+    ("smoothness", "Smoothness", &[
+        "excellent", "good", "intermediate", "bad", "very_bad", "horrible", "very_horrible", "impassable",
+    ]),
+    ("surface", "", &[
+        "asphalt", "compacted", "concrete", "dirt", "grass", "gravel", "ground", "paved", "paving_stones",
+        "unpaved", "sett", "cobblestone", "unhewn_cobblestone",
+    ]),
+    ("tracktype", "", &["grade1", "grade2", "grade3", "grade4", "grade5"]),
+];
+
+// concepts that aren't named after their value
+const SPECIAL_CONCEPTS: &[(&str, &str, &str)] = &[
+    ("highway", "footway", "osm:FootHighway"),
+    ("highway", "cycleway", "osm:CycleHighway"),
+    ("highway", "crossing", "osm:HighwayCrossing"),
+    ("oneway", "yes", "osm:InOrder"),
+    ("oneway", "no", "osm:Bidirectional"),
+    ("oneway", "-1", "osm:InReverseOrder"),
+    ("oneway_bicycle", "yes", "osm:InOrder"),
+    ("oneway_bicycle", "no", "osm:Bidirectional"),
+    ("oneway_bicycle", "-1", "osm:InReverseOrder"),
+    ("construction", "yes", "osm:UnderConstruction"),
+];
+
+fn camel_case(value: &str) -> String {
+    value
+        .split('_')
+        .map(|part| {
+            let mut characters = part.chars();
+            match characters.next() {
+                Some(first) => first.to_uppercase().chain(characters).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+fn get_concept(key: &str, value: &str) -> Option<String> {
+    if ACCESS_KEYS.contains(&key) {
+        match value {
+            "yes" => return Some("osm:FreeAccess".to_owned()),
+            "no" => return Some("osm:NoAccess".to_owned()),
+            _ => {}
+        }
+    }
+    if let Some((_, _, concept)) = SPECIAL_CONCEPTS.iter().find(|(k, v, _)| *k == key && *v == value) {
+        return Some((*concept).to_owned());
+    }
+
+    // all access keys share the values of `access`
+    let vocabulary_key = if ACCESS_KEYS.contains(&key) { "access" } else { key };
+    let (_, suffix, values) = VOCABULARY.iter().find(|(k, _, _)| *k == vocabulary_key)?;
+    if values.contains(&value) {
+        Some(format!("osm:{}{}", camel_case(value), suffix))
+    } else {
+        None
+    }
+}
+
+// the way routable tiles describe tags, the defined ones as properties and the others as `key=value`
+fn convert_tags(tags: &[(String, String)]) -> (BTreeMap<String, String>, Vec<String>) {
+    let mut defined = BTreeMap::new();
+    let mut undefined = Vec::new();
+    for (key, value) in tags {
+        let term = format!("osm:{}", key);
+        if LITERAL_KEYS.contains(&key.as_str()) || key.starts_with("restriction:") {
+            defined.insert(term, value.clone());
+            continue;
+        }
+
+        let concept = if IRI_VALUED_TERMS.contains(&term.as_str()) {
+            get_concept(key, value)
+        } else {
+            None
+        };
+        match concept {
+            Some(concept) => {
+                defined.insert(term, concept);
+            }
+            None => undefined.push(format!("{}={}", key, value)),
+        }
+    }
+    (defined, undefined)
+}
+
+fn get_node_iri(id: i64) -> String {
+    format!("http://www.openstreetmap.org/node/{}", id)
+}
+
+fn get_way_iri(id: i64) -> String {
+    format!("http://www.openstreetmap.org/way/{}", id)
+}
+
+fn get_relation_iri(id: i64) -> String {
+    format!("http://www.openstreetmap.org/relation/{}", id)
+}
+
+fn create_node(node: &OsmNode) -> Node {
+    let (tags, undefined_tags) = convert_tags(&node.tags);
+    Node::new(get_node_iri(node.id), node.lat, node.lon, tags, undefined_tags)
+}
+
+fn create_way(way: &OsmWay, nodes: &[&OsmNode], speeds: &SpeedTable) -> Way {
+    let (tags, undefined_tags) = convert_tags(&way.tags);
+    // unparsable values stay available as a tag, like they do when fetching tiles
+    let max_speed = tags.get("osm:maxspeed").and_then(|value| speeds.interpret(value).get_limit());
+    let nodes = nodes.iter().map(|node| get_node_iri(node.id)).collect();
+    Way::new(get_way_iri(way.id), nodes, None, max_speed, tags, undefined_tags)
+}
+
+fn create_relation(relation: &OsmRelation) -> Relation {
+    let members = relation
+        .members
+        .iter()
+        .map(|member| {
+            let id = match member.kind {
+                MemberType::Node => get_node_iri(member.id),
+                MemberType::Way => get_way_iri(member.id),
+                MemberType::Relation => get_relation_iri(member.id),
+            };
+            Member::new(id, member.role.clone())
+        })
+        .collect();
+    let (tags, undefined_tags) = convert_tags(&relation.tags);
+    Relation::new(get_relation_iri(relation.id), members, tags, undefined_tags)
+}
+
+fn is_routable(way: &OsmWay) -> bool {
+    way.tags.iter().any(|(key, _)| key == "highway")
+}
+
+// only turn restrictions are needed for routing, other relations can span entire countries
+fn is_restriction(relation: &OsmRelation) -> bool {
+    relation.tags.iter().any(|(key, value)| key == "type" && value == "restriction")
+}
+
+// tiles are built and handed over this many at a time, so only a batch of them is in memory at once
+const BATCH_SIZE: usize = 256;
+
+// cuts an extract into the given tiles, the same way routable tiles are cut:
+// every highway is part of each tile it has a node in, along with all of its nodes.
+// Tile membership only comes from node positions, so a segment that crosses a tile
+// without having a node in it isn't part of that tile.
+// The tiles are passed to `write` in batches, along with how many tiles get written in total.
+pub fn cut_extract<F>(path: &str, coords: &[TileCoordinate], speeds: &SpeedTable, mut write: F) -> Result<(), ExtractError>
+where
+    F: FnMut(Vec<Tile>, usize),
+{
+    let zoom = match coords.first() {
+        Some(coord) => coord.zoom,
+        None => return Ok(()),
+    };
+    let targets: BTreeSet<TileCoordinate> = coords.iter().copied().collect();
+
+    // extracts store the nodes first, so the ones that are needed are only known after a first pass
+    let mut ways = Vec::new();
+    let mut restrictions = Vec::new();
+    read_extract(path, &mut |element| match element {
+        OsmElement::Way(way) if is_routable(&way) => ways.push(way),
+        OsmElement::Relation(relation) if is_restriction(&relation) => restrictions.push(relation),
+        _ => {}
+    })?;
+
+    let needed: HashSet<i64> = ways.iter().flat_map(|way| way.nodes.iter().copied()).collect();
+    let mut nodes = HashMap::new();
+    read_extract(path, &mut |element| {
+        if let OsmElement::Node(node) = element {
+            if needed.contains(&node.id) {
+                nodes.insert(node.id, node);
+            }
+        }
+    })?;
+
+    // only the tile memberships are collected up front, the tiles themselves are built per batch
+    let mut tile_ways: BTreeMap<TileCoordinate, Vec<usize>> = BTreeMap::new();
+    let mut way_tiles: HashMap<i64, Vec<TileCoordinate>> = HashMap::new();
+    for (index, way) in ways.iter().enumerate() {
+        // nodes outside of the extract are left out
+        let way_nodes: Vec<&OsmNode> = way.nodes.iter().filter_map(|id| nodes.get(id)).collect();
+        if way_nodes.len() < 2 {
+            continue;
+        }
+
+        let coords: BTreeSet<TileCoordinate> = way_nodes
+            .iter()
+            .map(|node| deg2num(node.lat, node.lon, zoom))
+            .filter(|coord| targets.contains(coord))
+            .collect();
+        for coord in coords.iter() {
+            tile_ways.entry(*coord).or_default().push(index);
+        }
+        if !coords.is_empty() {
+            way_tiles.insert(way.id, coords.into_iter().collect());
+        }
+    }
+
+    // restrictions always have ways as members, so they end up in the tiles of those ways
+    let mut tile_restrictions: BTreeMap<TileCoordinate, Vec<usize>> = BTreeMap::new();
+    for (index, restriction) in restrictions.iter().enumerate() {
+        let coords: BTreeSet<&TileCoordinate> = restriction
+            .members
+            .iter()
+            .filter(|member| member.kind == MemberType::Way)
+            .filter_map(|member| way_tiles.get(&member.id))
+            .flatten()
+            .collect();
+        for coord in coords {
+            tile_restrictions.entry(*coord).or_default().push(index);
+        }
+    }
+
+    let coords: Vec<&TileCoordinate> = tile_ways.keys().collect();
+    for batch in coords.chunks(BATCH_SIZE) {
+        let tiles = batch
+            .iter()
+            .map(|coord| {
+                let mut tile = Tile::new(**coord, BTreeMap::new(), BTreeMap::new(), BTreeMap::new());
+                for way in tile_ways[coord].iter().map(|index| &ways[*index]) {
+                    let way_nodes: Vec<&OsmNode> = way.nodes.iter().filter_map(|id| nodes.get(id)).collect();
+                    for node in way_nodes.iter() {
+                        tile.nodes.entry(get_node_iri(node.id)).or_insert_with(|| create_node(node));
+                    }
+                    let converted = create_way(way, &way_nodes, speeds);
+                    tile.ways.insert(converted.get_id().to_owned(), converted);
+                }
+                for restriction in tile_restrictions.get(coord).into_iter().flatten() {
+                    let converted = create_relation(&restrictions[*restriction]);
+                    tile.relations.insert(converted.get_id().to_owned(), converted);
+                }
+                tile
+            })
+            .collect();
+        write(tiles, coords.len());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use crate::entities::weighted_tile::WeightedTile;
+use std::fmt::Write;
+
+pub fn describe_binary_tile(tile: &WeightedTile, samples: usize) -> String {
+    let mut result = String::new();
+    let header = &tile.header;
+    writeln!(result, "format: {} v{} ({})", header.magic, header.version, header.generator).unwrap();
+    writeln!(
+        result,
+        "tile: {}/{}/{}",
+        header.coordinate.zoom, header.coordinate.x, header.coordinate.y
+    )
+    .unwrap();
+    writeln!(result, "profile: {} ({:016x})", header.profile_name, header.profile_hash).unwrap();
+    let metrics: Vec<String> = header
+        .metrics
+        .iter()
+        .map(|metric| format!("{} ({})", metric.get_name(), metric.get_unit()))
+        .collect();
+    writeln!(result, "metrics: {}", metrics.join(", ")).unwrap();
+    for (key, value) in header.parameters.iter() {
+        writeln!(result, "{}: {}", key, value).unwrap();
+    }
+    writeln!(result, "nodes: {}", tile.locations.len()).unwrap();
+    writeln!(result, "labels: {}", tile.labels.len()).unwrap();
+    writeln!(result, "edges: {}", tile.edges.len()).unwrap();
+    writeln!(result, "boundary nodes: {}", tile.boundary_nodes.len()).unwrap();
+    writeln!(result, "forbidden turns: {}", tile.forbidden_turns.len()).unwrap();
+    if let Some(references) = &tile.way_references {
+        writeln!(result, "ways: {}", references.ways.len()).unwrap();
+    }
+
+    if !tile.edges.is_empty() {
+        let total: u64 = tile.edges.iter().map(|edge| edge.weight).sum();
+        let min = tile.edges.iter().map(|edge| edge.weight).min().unwrap();
+        let max = tile.edges.iter().map(|edge| edge.weight).max().unwrap();
+        writeln!(
+            result,
+            "weights: min {}, max {}, mean {:.1}",
+            min,
+            max,
+            total as f64 / tile.edges.len() as f64
+        )
+        .unwrap();
+    }
+
+    for (index, edge) in tile.edges.iter().enumerate().take(samples) {
+        let weights: Vec<String> = header
+            .metrics
+            .iter()
+            .filter_map(|metric| tile.get_weight(index, *metric).map(|weight| (metric, weight)))
+            .map(|(metric, weight)| format!("{} {}", weight, metric.get_unit()))
+            .collect();
+        let weights = weights.join(", ");
+
+        let from = tile.locations.get(edge.from);
+        let to = tile.locations.get(edge.to);
+        match (from, to) {
+            (Some(from), Some(to)) => write!(
+                result,
+                "{} ({}, {}) -> {} ({}, {}): {}",
+                from.id, from.lat, from.lon, to.id, to.lat, to.lon, weights
+            ),
+            _ => write!(result, "{} -> {}: {} (dangling)", edge.from, edge.to, weights),
+        }
+        .unwrap();
+
+        // edges that leave the tile continue in a neighbouring one
+        if let Some(coord) = tile.get_boundary_tile(edge.to) {
+            write!(result, ", continues in {}/{}/{}", coord.zoom, coord.x, coord.y).unwrap();
+        }
+
+        match tile.way_references.as_ref().and_then(|r| r.get_way(index)) {
+            Some(way) => writeln!(result, " via {}", way.id),
+            None => writeln!(result),
+        }
+        .unwrap();
+    }
+
+    result
+}
@@ -1,4 +1,5 @@
-use crate::{io::{tiles::{write_derived_tile, parse_tile}, get_tile_path}, entities::tile_coord::TileCoordinate};
+use crate::{io::{tiles::{write_derived_tile, parse_tile, ParseMode, TileDescription, TileError}, get_tile_path}, entities::tile_coord::TileCoordinate};
+use crate::util::maxspeed::SpeedTable;
 use anyhow::{Result};
 use std::path::Path;
 
@@ -6,7 +7,10 @@ pub fn fetch_tile(
     data_source: &str,
     target_dir: &str,
     coord: &TileCoordinate,
-) -> Result<()> {
+    mode: ParseMode,
+    speeds: &SpeedTable,
+    description: &TileDescription,
+) -> Result<Vec<TileError>> {
     let client = reqwest::blocking::Client::builder()
         .gzip(true)
         .build()?;
@@ -14,11 +18,12 @@ pub fn fetch_tile(
     let uri = format!("{}/{}/{}/{}", data_source, coord.zoom, coord.x, coord.y);
     let target_path = get_tile_path(target_dir, coord);
     if !Path::new(&target_path).exists() {
-        let response = client.get(&uri).send()?;
+        let response = client.get(&uri).send()?.error_for_status()?;
         let content =  response.text()?;
-        let tile = parse_tile(coord, content)?;
-        write_derived_tile(tile, &target_path).unwrap();
+        let (tile, warnings) = parse_tile(coord, content, mode, speeds)?;
+        write_derived_tile(tile, &target_path, description).unwrap();
+        return Ok(warnings);
     }
     
-    Ok(())
+    Ok(Vec::new())
 }
\ No newline at end of file
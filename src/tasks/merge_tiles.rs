@@ -1,6 +1,7 @@
 use crate::entities::tile::Tile;
-use crate::io::tiles::load_cached_tile;
+use crate::io::tiles::{load_cached_tile, TileError};
 use crate::entities::node::Node;
+use crate::entities::relation::Relation;
 use crate::entities::tile_coord::TileCoordinate;
 use crate::entities::way::Way;
 use crate::util::deg2num;
@@ -71,17 +72,23 @@ pub fn create_merged_tile<'a>(
     root_dir: &str,
     source_coords: &'a [TileCoordinate],
     target_coord: &'a TileCoordinate,
-) -> Tile {
+) -> (Tile, Vec<TileError>) {
     let mut way_proxies: BTreeMap<String, WayProxy> = BTreeMap::new();
     let mut way_examples: BTreeMap<String, Way> = BTreeMap::new();
     let mut all_nodes: BTreeMap<String, Node> = BTreeMap::new();
+    let mut all_relations: BTreeMap<String, Relation> = BTreeMap::new();
+    let mut warnings = Vec::new();
 
     for source_coord in source_coords {
         let base_tile = load_cached_tile(source_coord, root_dir);
-        if let Ok(tile) = base_tile {
+        if let Ok((tile, tile_warnings)) = base_tile {
+            warnings.extend(tile_warnings);
             for (node_id, node) in tile.get_nodes() {
                 all_nodes.insert(node_id.to_string(), node.clone());
             }
+            for (relation_id, relation) in tile.get_relations() {
+                all_relations.insert(relation_id.clone(), relation.clone());
+            }
             for (way_id, way) in tile.get_ways() {
                 way_examples.insert(way_id.clone(), way.clone());
                 match way_proxies.entry(way_id.clone()) {
@@ -116,7 +123,7 @@ pub fn create_merged_tile<'a>(
                 break;
             }
             for candidate_coord in tile_coords {
-                if let Ok(tile) = load_cached_tile(&candidate_coord, root_dir) {
+                if let Ok((tile, _)) = load_cached_tile(&candidate_coord, root_dir) {
                     if let Some(way) = tile.get_ways().get(way_id) {
                         proxy.add_way(way);
                         for node_id in way.get_nodes() {
@@ -138,7 +145,7 @@ pub fn create_merged_tile<'a>(
         let nodes = proxy.get_node_ids();
         if !nodes.is_empty() {
             let example_way = way_examples.get(&way_id).unwrap();
-            let way = Way::new(
+            let mut way = Way::new(
                 way_id.clone(),
                 nodes,
                 None,
@@ -146,9 +153,15 @@ pub fn create_merged_tile<'a>(
                 example_way.get_tags().clone(),
                 example_way.get_undefined_tags().to_vec(),
             );
+            way.set_properties(example_way.get_properties().clone());
             all_ways.insert(way_id, way);
         };
     }
 
-    Tile::new(*target_coord, all_nodes, all_ways)
+    let all_relations = all_relations
+        .into_iter()
+        .filter(|(_, relation)| relation.touches(&all_nodes, &all_ways))
+        .collect();
+
+    (Tile::new(*target_coord, all_nodes, all_ways, all_relations), warnings)
 }
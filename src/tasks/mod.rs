@@ -3,4 +3,7 @@ pub mod reduce_transit;
 pub mod merge_tiles;
 pub mod reduce_contract;
 pub mod reduce_binary;
-pub mod load_tile;
\ No newline at end of file
+pub mod load_tile;
+pub mod inspect_binary;
+pub mod export_geojson;
+pub mod import_extract;
@@ -1,22 +1,130 @@
 use crate::entities::weighted_tile::Location;
 use crate::entities::tile::Tile;
-use crate::io::tiles::load_tile;
-use crate::entities::weighted_tile::{DirectedEdge, WeightedTile};
-use crate::entities::profile::Profile;
+use crate::io::tiles::{load_tile, TileError};
+use crate::entities::weighted_tile::{BoundaryNode, DirectedEdge, ForbiddenTurn, Metric, TileHeader, WayReference, WayReferences, WeightedTile};
+use crate::entities::relation::{RestrictionKind, TurnRestriction};
+use crate::entities::way::Way;
+use crate::entities::node::Node;
+use crate::util::edge_nodes::get_edge_nodes;
+use crate::util::haversine::get_distance;
+use crate::util::{deg2num, get_tile_edges};
+use crate::entities::compiled_profile::{CompiledProfile, NodeProfileCache};
 use crate::entities::{segment::Segment, tile_coord::TileCoordinate};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+// tags kept on way references besides the profile's concepts, for route instructions
+const REFERENCE_TAGS: &[&str] = &["osm:name", "osm:ref"];
+
+#[derive(Debug, Clone)]
+pub struct BinaryTileOptions {
+    pub way_references: bool,
+    pub metrics: Vec<Metric>, // the first one becomes the edge weight
+}
+
+impl Default for BinaryTileOptions {
+    fn default() -> BinaryTileOptions {
+        BinaryTileOptions {
+            way_references: false,
+            metrics: vec![Metric::Cost],
+        }
+    }
+}
+
+fn create_way_reference(way: &Way, nodes: &BTreeMap<String, Node>, concepts: &HashSet<String>) -> WayReference {
+    let tags = way
+        .get_tags()
+        .iter()
+        .filter(|(key, _)| concepts.contains(*key) || REFERENCE_TAGS.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    let length = way
+        .get_segments()
+        .iter()
+        .filter_map(|segment| Some(get_distance(nodes.get(segment.from)?, nodes.get(segment.to)?)))
+        .sum::<f64>()
+        * 1000.;
+
+    WayReference {
+        id: way.get_id().to_owned(),
+        tags,
+        length,
+    }
+}
+
+// (from label, to label, weight per metric, way index, way id), sorted by from label
+type Edge<'a> = (usize, usize, Vec<u64>, usize, &'a str);
+
+// expands the restrictions into pairs of edges, a mandatory turn forbids all other turns
+fn get_forbidden_turns(
+    edges: &[Edge],
+    labels: &BTreeMap<String, usize>,
+    restrictions: &[TurnRestriction],
+) -> Vec<ForbiddenTurn> {
+    // (from way, via node) to the restrictions that start there
+    let mut starts: HashMap<(&str, usize), Vec<&TurnRestriction>> = HashMap::new();
+    for restriction in restrictions {
+        if let Some(via) = labels.get(restriction.via) {
+            starts.entry((restriction.from, *via)).or_default().push(restriction);
+        }
+    }
+
+    let mut first_edges = vec![edges.len(); labels.len() + 1];
+    for (index, (from, ..)) in edges.iter().enumerate().rev() {
+        first_edges[*from] = index;
+    }
+    for label in (0..labels.len()).rev() {
+        first_edges[label] = first_edges[label].min(first_edges[label + 1]);
+    }
+
+    let mut result = Vec::new();
+    for (index, (_, via, _, _, way)) in edges.iter().enumerate() {
+        let restrictions = match starts.get(&(*way, *via)) {
+            Some(restrictions) => restrictions,
+            None => continue,
+        };
+
+        // the edges leaving the via node
+        let outgoing = edges.iter().enumerate().take(first_edges[via + 1]).skip(first_edges[*via]);
+        for (next, (.., next_way)) in outgoing {
+            let next_way = *next_way;
+            let forbidden = restrictions.iter().any(|restriction| match restriction.kind {
+                RestrictionKind::Prohibitory => restriction.to == next_way,
+                RestrictionKind::Mandatory => restriction.to != next_way,
+            });
+            if forbidden {
+                result.push(ForbiddenTurn { from: index, to: next });
+            }
+        }
+    }
+    result
+}
 
 pub fn create_binary_tile<'a>(
     root_dir: &str,
     coord: &'a TileCoordinate,
-    profile: &Profile,
-) -> WeightedTile {
-    let base_tile = load_tile(coord, root_dir);
+    profile: &CompiledProfile,
+    options: &BinaryTileOptions,
+) -> (WeightedTile, Vec<TileError>) {
+    let (base_tile, warnings) = match load_tile(coord, root_dir) {
+        Ok((tile, warnings)) => (Some(tile), warnings),
+        Err(_) => (None, Vec::new()),
+    };
     let mut locations = Vec::new();
     let mut labels = BTreeMap::new();
     let mut edges = Vec::new();
+    let mut references = Vec::new();
+    let concepts = profile.get_profile().get_used_concepts();
+    let metrics = if options.metrics.is_empty() {
+        vec![Metric::Cost]
+    } else {
+        options.metrics.clone()
+    };
 
-    if let Ok(tile) = base_tile {  
+    let mut boundary_nodes = Vec::new();
+    let mut forbidden_turns = Vec::new();
+
+    if let Some(tile) = base_tile.as_ref() {
+        let outside = get_edge_nodes(tile, get_tile_edges(coord));
         let Tile {nodes, ways, .. } = tile;
         let mut reverse_labels = BTreeMap::new();
 
@@ -26,38 +134,97 @@ pub fn create_binary_tile<'a>(
             labels.insert(id.to_owned(), label);
         }
 
+        let mut node_profiles = NodeProfileCache::new(profile);
         for way in ways.values() {
-            if !profile.has_access(way) {
+            let way_profile = profile.evaluate_way(way);
+            if !way_profile.access {
                 continue;
             }
 
+            let way_index = references.len();
+            if options.way_references {
+                references.push(create_way_reference(way, nodes, &concepts));
+            }
+
+            let direction = way_profile.direction;
             for edge in way.get_segments() {
                 let Segment { from, to } = edge;
                 let from_node = nodes.get(from).expect("Corrupted tile");
                 let to_node = nodes.get(to).expect("Corrupted tile");
+                let from_profile = node_profiles.get(from_node);
+                let to_profile = node_profiles.get(to_node);
 
-                if profile.is_obstacle(from_node) || profile.is_obstacle(to_node) {
+                if from_profile.obstacle || to_profile.obstacle {
                     continue;
                 }
 
                 let to_label = labels.get(edge.to).unwrap();
                 let from_label = labels.get(edge.from).unwrap();
 
-                if !profile.is_one_way(way) {
-                    let backward_cost = profile.get_cost(to_node, from_node, way);
-                    edges.push(DirectedEdge::new(*to_label, *from_label, backward_cost as u64));
+                if direction.allows_backward() {
+                    let backward_weights: Vec<u64> = metrics
+                        .iter()
+                        .map(|m| profile.get_weight(*m, to_node, from_node, &way_profile, &from_profile))
+                        .map(|weight| weight.round() as u64)
+                        .collect();
+                    edges.push((*to_label, *from_label, backward_weights, way_index, way.get_id()));
                 }
 
-                let forward_cost = profile.get_cost(from_node, to_node, way);
-                edges.push(DirectedEdge::new(*from_label, *to_label, forward_cost as u64));
+                if direction.allows_forward() {
+                    let forward_weights: Vec<u64> = metrics
+                        .iter()
+                        .map(|m| profile.get_weight(*m, from_node, to_node, &way_profile, &to_profile))
+                        .map(|weight| weight.round() as u64)
+                        .collect();
+                    edges.push((*from_label, *to_label, forward_weights, way_index, way.get_id()));
+                }
             }
         }
 
-        for id in reverse_labels.values() {
+        for (label, id) in reverse_labels.iter() {
             let node = nodes.get(id).unwrap();
             let location = Location {id: node.get_id().to_owned(), lat: node.get_lat(), lon: node.get_long()};
             locations.push(location);
+
+            if outside.contains(id) {
+                let tile = deg2num(node.get_lat(), node.get_long(), coord.zoom);
+                boundary_nodes.push(BoundaryNode { node: *label, tile });
+            }
         }
+
+        // grouped by source node, the way the compact encoding stores them
+        edges.sort_by_key(|(from, ..)| *from);
+        let modes = profile.get_profile().get_transport_modes();
+        forbidden_turns = get_forbidden_turns(&edges, &labels, &tile.get_turn_restrictions(&modes));
+    }
+
+    let mut directed_edges = Vec::with_capacity(edges.len());
+    let mut secondary_weights = vec![Vec::with_capacity(edges.len()); metrics.len() - 1];
+    let mut edge_ways = Vec::with_capacity(edges.len());
+    for (from, to, weights, way_index, _) in edges {
+        directed_edges.push(DirectedEdge::new(from, to, weights[0]));
+        for (list, weight) in secondary_weights.iter_mut().zip(weights[1..].iter()) {
+            list.push(*weight);
+        }
+        edge_ways.push(way_index);
+    }
+
+    let mut header = TileHeader::new(*coord, profile.get_profile().get_source());
+    header.metrics = metrics;
+    header.parameters.insert("task".to_owned(), "reduce_binary".to_owned());
+    if options.way_references {
+        header.parameters.insert("way_references".to_owned(), "true".to_owned());
+    }
+
+    let mut tile = WeightedTile::new(header, locations, labels, directed_edges);
+    tile.secondary_weights = secondary_weights;
+    tile.boundary_nodes = boundary_nodes;
+    tile.forbidden_turns = forbidden_turns;
+    if options.way_references {
+        tile.way_references = Some(WayReferences {
+            ways: references,
+            edges: edge_ways,
+        });
     }
-    WeightedTile::new(locations, labels, edges)
+    (tile, warnings)
 }
\ No newline at end of file
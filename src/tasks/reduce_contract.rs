@@ -1,4 +1,4 @@
-use crate::io::tiles::load_tile;
+use crate::io::tiles::{load_tile, TileError};
 use crate::entities::node::Node;
 use crate::entities::tile::Tile;
 use crate::entities::tile_coord::TileCoordinate;
@@ -67,14 +67,16 @@ fn contract_way(way: &Way, tile: &Tile, useful_nodes: &BTreeSet<String>) -> Way
         previous_node = current_node;
     }
 
-    Way::new(
+    let mut contracted = Way::new(
         way.get_id().to_string(),
         nodes,
         Some(distances),
         *way.get_max_speed(),
         way.get_tags().clone(),
         way.get_undefined_tags().to_vec(),
-    )
+    );
+    contracted.set_properties(way.get_properties().clone());
+    contracted
 }
 
 // not used at the moment
@@ -83,13 +85,16 @@ fn contract_way(way: &Way, tile: &Tile, useful_nodes: &BTreeSet<String>) -> Way
 pub fn create_contracted_tile<'a>(
     root_dir: &str,
     coord: &'a TileCoordinate,
-) -> Tile {
+) -> (Tile, Vec<TileError>) {
     let base_tile = load_tile(coord, root_dir);
     let bounds = get_tile_edges(coord);
 
     let mut reduced_ways: BTreeMap<String, Way> = BTreeMap::new();
     let mut reduced_nodes: BTreeMap<String, Node> = BTreeMap::new();
-    if let Ok(tile) = base_tile {
+    let mut reduced_relations = BTreeMap::new();
+    let mut warnings = Vec::new();
+    if let Ok((tile, tile_warnings)) = base_tile {
+        warnings = tile_warnings;
         let useful_nodes = get_useful_nodes(&tile, bounds);
 
         for (node_id, node) in tile.get_nodes() {
@@ -102,7 +107,9 @@ pub fn create_contracted_tile<'a>(
             let new_way = contract_way(way, &tile, &useful_nodes);
             reduced_ways.insert(way_id.clone(), new_way);
         }
+
+        reduced_relations = tile.get_touching_relations(&reduced_nodes, &reduced_ways);
     }
 
-    Tile::new(*coord, reduced_nodes, reduced_ways)
+    (Tile::new(*coord, reduced_nodes, reduced_ways, reduced_relations), warnings)
 }
@@ -1,6 +1,6 @@
-use crate::io::tiles::load_tile;
+use crate::io::tiles::{load_tile, TileError};
 use crate::entities::node::Node;
-use crate::entities::profile::Profile;
+use crate::entities::compiled_profile::CompiledProfile;
 use crate::entities::segment::Segment;
 use crate::entities::tile::Tile;
 use crate::entities::tile_coord::TileCoordinate;
@@ -10,26 +10,39 @@ use std::collections::BTreeMap;
 pub fn create_profile_tile<'a>(
     root_dir: &str,
     coord: &'a TileCoordinate,
-    profile: &Profile,
-) -> Tile {
+    profile: &CompiledProfile,
+) -> (Tile, Vec<TileError>) {
     let tile_result = load_tile(coord, root_dir);
     let mut reduced_ways: BTreeMap<String, Way> = BTreeMap::new();
     let mut reduced_nodes: BTreeMap<String, Node> = BTreeMap::new();
-    let concepts = profile.get_used_concepts();
+    let mut reduced_relations = BTreeMap::new();
+    let mut warnings = Vec::new();
+    let concepts = profile.get_profile().get_used_concepts();
+    let open_predicates = profile.get_profile().get_open_predicates();
+    let open_objects = profile.get_profile().get_open_objects();
 
-    if let Ok(tile) = tile_result {
+    if let Ok((tile, tile_warnings)) = tile_result {
+        warnings = tile_warnings;
         for (way_id, way) in tile.get_ways() {
             if profile.has_access(way) {
                 let mut new_tags = BTreeMap::new();
                 for tag in way.get_tags().iter() {
-                    if concepts.contains(tag.0) && concepts.contains(tag.1) {
+                    let predicate_match = concepts.contains(tag.0)
+                        && (concepts.contains(tag.1) || open_predicates.contains(tag.0));
+                    if predicate_match || open_objects.contains(tag.1) {
                         new_tags.insert(tag.0.to_owned(), tag.1.to_owned());
                     }
                 }
                 if let Some(name) = way.get_tags().get("osm:name") {
                     new_tags.insert("osm:name".to_owned(), name.to_string());
                 }
-                let new_way = Way::new(
+                let properties = way
+                    .get_properties()
+                    .iter()
+                    .filter(|(key, _)| new_tags.contains_key(*key))
+                    .map(|(key, property)| (key.clone(), property.clone()))
+                    .collect();
+                let mut new_way = Way::new(
                     way_id.to_owned(),
                     way.get_nodes().to_owned(),
                     None,
@@ -37,6 +50,7 @@ pub fn create_profile_tile<'a>(
                     new_tags,
                     Vec::new(),
                 );
+                new_way.set_properties(properties);
     
                 reduced_ways.insert(way_id.clone(), new_way);
     
@@ -49,7 +63,9 @@ pub fn create_profile_tile<'a>(
                 }
             }
         }
+
+        reduced_relations = tile.get_touching_relations(&reduced_nodes, &reduced_ways);
     }
     
-    Tile::new(*coord, reduced_nodes, reduced_ways)
+    (Tile::new(*coord, reduced_nodes, reduced_ways, reduced_relations), warnings)
 }
@@ -1,11 +1,13 @@
 use crate::io::tiles::load_cached_tile;
 use crate::entities::graph::Graph;
+use crate::entities::relation::TurnRestriction;
+use crate::entities::turn_graph::TurnGraph;
 use crate::entities::node::Node;
-use crate::entities::profile::Profile;
+use crate::entities::compiled_profile::CompiledProfile;
 use crate::entities::tile::Tile;
 use crate::entities::tile_coord::TileCoordinate;
 use crate::entities::way::Way;
-use crate::io::tiles::load_tile;
+use crate::io::tiles::{load_tile, TileError};
 use crate::util::deg2num;
 use crate::util::edge_nodes::get_edge_nodes;
 use crate::util::get_tile_edges;
@@ -39,39 +41,71 @@ fn adjacent_tiles(coord: &TileCoordinate, target_zoom: u32) -> Vec<TileCoordinat
     result
 }
 
-fn update_graph<'a>(graph: &mut Graph<'a>, tile: &'a Tile, profile: &Profile) {
-    let weights = tile.get_weighted_segments(profile);
-    graph.add_edges(weights);
+// the edge-based graph is slower, so it's only used when there are turn restrictions to respect
+enum TransitGraph<'a> {
+    Nodes(Graph<'a>),
+    Edges(TurnGraph<'a>),
+}
+
+impl<'a> TransitGraph<'a> {
+    fn new(tiles: &[&'a Tile], profile: &CompiledProfile) -> TransitGraph<'a> {
+        let modes = profile.get_profile().get_transport_modes();
+        let restrictions: Vec<TurnRestriction> = tiles
+            .iter()
+            .flat_map(|tile| tile.get_turn_restrictions(&modes))
+            .collect();
+
+        if restrictions.is_empty() {
+            let mut graph = Graph::new(Vec::new());
+            for tile in tiles {
+                graph.add_edges(tile.get_weighted_segments(profile));
+            }
+            TransitGraph::Nodes(graph)
+        } else {
+            let mut graph = TurnGraph::new(Vec::new(), &restrictions);
+            for tile in tiles {
+                graph.add_edges(tile.get_weighted_way_segments(profile));
+            }
+            TransitGraph::Edges(graph)
+        }
+    }
+
+    fn necessary_nodes(&self, from: &str, to: Vec<&String>) -> BTreeSet<String> {
+        match self {
+            TransitGraph::Nodes(graph) => graph.necessary_nodes(from, to),
+            TransitGraph::Edges(graph) => graph.necessary_nodes(from, to),
+        }
+    }
 }
 
 pub fn create_indirect_transit_tile<'a>(
     root_dir: &str,
     padding_zoom: u32,
     coord: &'a TileCoordinate,
-    profile: &Profile,
-) -> Tile {
+    profile: &CompiledProfile,
+) -> (Tile, Vec<TileError>) {
     // build base graph
     let base_tile = load_cached_tile(coord, root_dir);
     let mut reduced_ways = BTreeMap::new();
     let mut reduced_nodes = BTreeMap::new();
+    let mut reduced_relations = BTreeMap::new();
+    let mut warnings = Vec::new();
 
-    if let Ok(tile) = base_tile {
-        let weights = tile.get_weighted_segments(profile);
-        let mut graph = Graph::new(weights);
+    // the neighbours report their own warnings when they're processed
+    if let Ok((tile, tile_warnings)) = base_tile {
+        warnings = tile_warnings;
 
         // collect neighboring tiles
         let neighbor_coords = adjacent_tiles(coord, padding_zoom);
         let neighbors: Vec<Tile> = neighbor_coords
             .iter()
-            .map(|v| load_cached_tile(v, root_dir))
-            .filter(|v| v.is_ok())
-            .map(|v| v.unwrap())
+            .filter_map(|v| load_cached_tile(v, root_dir).ok())
+            .map(|(neighbor, _)| neighbor)
             .collect();
 
-        // add neighboring tile data
-        neighbors
-            .iter()
-            .for_each(|t| update_graph(&mut graph, t, profile));
+        let mut tiles = vec![&tile];
+        tiles.extend(neighbors.iter());
+        let graph = TransitGraph::new(&tiles, profile);
 
         // squeeze out the bounds of the edge tiles
         // bit of a shotgun approach, but should work
@@ -140,7 +174,7 @@ pub fn create_indirect_transit_tile<'a>(
                     reduced_nodes.insert(node_id.clone(), node);
                 }
 
-                let way = Way::new(
+                let mut reduced_way = Way::new(
                     way_id.clone(),
                     nodes,
                     None,
@@ -148,42 +182,52 @@ pub fn create_indirect_transit_tile<'a>(
                     way.get_tags().clone(),
                     way.get_undefined_tags().to_vec(),
                 );
-                reduced_ways.insert(way_id.clone(), way);
+                reduced_way.set_properties(way.get_properties().clone());
+                reduced_ways.insert(way_id.clone(), reduced_way);
             }
         }
+
+        reduced_relations = tile.get_touching_relations(&reduced_nodes, &reduced_ways);
     }
 
-    Tile::new(*coord, reduced_nodes, reduced_ways)
+    (Tile::new(*coord, reduced_nodes, reduced_ways, reduced_relations), warnings)
+}
+
+// the nodes on the shortest paths between all pairs of nodes outside of the tile
+pub fn get_necessary_nodes(tile: &Tile, coord: &TileCoordinate, profile: &CompiledProfile) -> BTreeSet<String> {
+    let graph = TransitGraph::new(&[tile], profile);
+
+    let bounds = get_tile_edges(coord);
+    let edge_nodes = get_edge_nodes(tile, bounds);
+    let mut necessary_nodes = BTreeSet::new();
+    for first_node in edge_nodes.iter() {
+        let new_nodes = graph.necessary_nodes(
+            first_node,
+            edge_nodes
+                .iter()
+                .filter(|id| *id != first_node)
+                .collect(),
+        );
+
+        necessary_nodes.extend(new_nodes);
+    }
+    necessary_nodes
 }
 
 pub fn create_transit_tile<'a>(
     root_dir: &str,
     coord: &'a TileCoordinate,
-    profile: &Profile,
-) -> Tile {
+    profile: &CompiledProfile,
+) -> (Tile, Vec<TileError>) {
     let base_tile = load_tile(coord, root_dir);
     let mut reduced_ways = BTreeMap::new();
     let mut reduced_nodes = BTreeMap::new();
+    let mut reduced_relations = BTreeMap::new();
+    let mut warnings = Vec::new();
 
-    if let Ok(tile) = base_tile {  
-        let weights = tile.get_weighted_segments(profile);
-        let graph = Graph::new(weights);
-
-        let bounds = get_tile_edges(coord);
-        let edge_nodes = get_edge_nodes(&tile, bounds);
-        let mut necessary_nodes = BTreeSet::new();
-        for first_node in edge_nodes.iter() {
-            let new_nodes = graph.necessary_nodes(
-                first_node,
-                edge_nodes
-                    .iter()
-                    .filter(|id| *id != first_node)
-                    .map(|id| id)
-                    .collect(),
-            );
-
-            necessary_nodes.extend(new_nodes);
-        }
+    if let Ok((tile, tile_warnings)) = base_tile {
+        warnings = tile_warnings;
+        let necessary_nodes = get_necessary_nodes(&tile, coord, profile);
 
         for (way_id, way) in tile.get_ways() {
             let mut first_node = way.get_nodes().len();
@@ -202,7 +246,7 @@ pub fn create_transit_tile<'a>(
                     reduced_nodes.insert(node_id.clone(), node);
                 }
 
-                let way = Way::new(
+                let mut reduced_way = Way::new(
                     way_id.clone(),
                     nodes,
                     None,
@@ -210,10 +254,13 @@ pub fn create_transit_tile<'a>(
                     way.get_tags().clone(),
                     way.get_undefined_tags().to_vec(),
                 );
-                reduced_ways.insert(way_id.clone(), way);
+                reduced_way.set_properties(way.get_properties().clone());
+                reduced_ways.insert(way_id.clone(), reduced_way);
             }
         }
+
+        reduced_relations = tile.get_touching_relations(&reduced_nodes, &reduced_ways);
     }
 
-    Tile::new(*coord, reduced_nodes, reduced_ways)
+    (Tile::new(*coord, reduced_nodes, reduced_ways, reduced_relations), warnings)
 }
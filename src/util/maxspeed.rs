@@ -0,0 +1,145 @@
+use crate::util::units::parse_speed;
+use std::collections::BTreeMap;
+use std::fs;
+use thiserror::Error;
+
+// implicit values that depend on the country, see https://wiki.openstreetmap.org/wiki/Default_speed_limits
+const DEFAULT_SPEEDS: &[(&str, &str)] = &[
+    ("walk", "6"),
+    ("BE:urban", "50"),
+    ("BE:rural", "90"),
+    ("BE-VLG:rural", "70"),
+    ("BE:motorway", "120"),
+    ("BE:trunk", "120"),
+    ("BE:zone30", "30"),
+    ("BE:cyclestreet", "30"),
+    ("BE:living_street", "20"),
+    ("NL:urban", "50"),
+    ("NL:rural", "80"),
+    ("NL:motorway", "100"),
+    ("NL:zone30", "30"),
+    ("NL:living_street", "15"),
+    ("DE:urban", "50"),
+    ("DE:rural", "100"),
+    ("DE:motorway", "none"),
+    ("DE:zone30", "30"),
+    ("DE:bicycle_road", "30"),
+    ("DE:living_street", "7"),
+    ("FR:urban", "50"),
+    ("FR:rural", "80"),
+    ("FR:motorway", "130"),
+    ("FR:zone30", "30"),
+    ("GB:nsl_restricted", "30 mph"),
+    ("GB:nsl_single", "60 mph"),
+    ("GB:nsl_dual", "70 mph"),
+    ("GB:motorway", "70 mph"),
+];
+
+#[derive(Error, Debug, Clone)]
+pub enum SpeedTableError {
+    #[error("Invalid file path `{0}`")]
+    NotAFile(String),
+    #[error("Invalid speed table `{0}`: {1}")]
+    InvalidTable(String, String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MaxSpeed {
+    Limit(f64), // in km/h
+    Unlimited,
+    Variable,   // e.g. electronic signs
+    Invalid,
+}
+
+impl MaxSpeed {
+    pub fn get_limit(self) -> Option<f64> {
+        match self {
+            MaxSpeed::Limit(speed) => Some(speed),
+            _ => None,
+        }
+    }
+}
+
+// maps symbolic maxspeed values, such as `BE:urban` or `walk`, to plain ones
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpeedTable {
+    values: BTreeMap<String, String>,
+}
+
+impl Default for SpeedTable {
+    fn default() -> SpeedTable {
+        let values = DEFAULT_SPEEDS
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        SpeedTable { values }
+    }
+}
+
+impl SpeedTable {
+    // the file contains a JSON object, e.g. {"BE:urban": "50", "GB:nsl_single": "60 mph"}
+    pub fn load(path: &str) -> Result<SpeedTable, SpeedTableError> {
+        let data = match fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(_) => return Err(SpeedTableError::NotAFile(path.to_owned())),
+        };
+
+        let values: BTreeMap<String, String> = serde_json::from_str(&data)
+            .map_err(|e| SpeedTableError::InvalidTable(path.to_owned(), e.to_string()))?;
+
+        let mut result = SpeedTable::default();
+        for (key, value) in values {
+            if SpeedTable::interpret_plain(&value) == MaxSpeed::Invalid {
+                let reason = format!("can't interpret `{}` for `{}`", value, key);
+                return Err(SpeedTableError::InvalidTable(path.to_owned(), reason));
+            }
+            result.values.insert(key, value);
+        }
+        Ok(result)
+    }
+
+    // "50;70" lists several limits, the lowest one is used
+    pub fn interpret(&self, value: &str) -> MaxSpeed {
+        let mut result = None;
+        for part in value.split(';') {
+            let speed = self.interpret_single(part.trim());
+            result = match (result, speed) {
+                (_, MaxSpeed::Invalid) => return MaxSpeed::Invalid,
+                (Some(MaxSpeed::Limit(a)), MaxSpeed::Limit(b)) => Some(MaxSpeed::Limit(a.min(b))),
+                (Some(MaxSpeed::Limit(a)), _) => Some(MaxSpeed::Limit(a)),
+                (_, speed) => Some(speed),
+            };
+        }
+        result.unwrap_or(MaxSpeed::Invalid)
+    }
+
+    fn interpret_single(&self, value: &str) -> MaxSpeed {
+        if let Some(implicit) = self.lookup(value) {
+            return SpeedTable::interpret_plain(implicit);
+        }
+        SpeedTable::interpret_plain(value)
+    }
+
+    // `BE-VLG:urban` falls back to `BE:urban`
+    fn lookup(&self, value: &str) -> Option<&str> {
+        if let Some(implicit) = self.values.get(value) {
+            return Some(implicit);
+        }
+
+        let separator = value.find(':')?;
+        let country = value[..separator].split('-').next()?;
+        let key = format!("{}{}", country, &value[separator..]);
+        self.values.get(&key).map(String::as_str)
+    }
+
+    fn interpret_plain(value: &str) -> MaxSpeed {
+        match value {
+            "none" => MaxSpeed::Unlimited,
+            "signals" | "variable" => MaxSpeed::Variable,
+            _ => match parse_speed(value) {
+                Some(speed) if speed > 0. => MaxSpeed::Limit(speed),
+                _ => MaxSpeed::Invalid,
+            },
+        }
+    }
+}
@@ -1,6 +1,11 @@
 pub mod edge_nodes;
+pub mod hash;
 pub mod haversine;
+pub mod maxspeed;
+pub mod units;
+pub mod varint;
 
+use crate::entities::area::Area;
 use crate::entities::tile_coord::TileCoordinate;
 
 pub fn num2deg(x: u32, y: u32, zoom: u32) -> [f64; 2] {
@@ -31,9 +36,9 @@ pub fn get_tile_edges(coords: &TileCoordinate) -> [f64; 4] {
     [east, north, west, south]
 }
 
-pub fn get_tile_coords(lats: [f64; 2], lons: [f64; 2], zoom: u32) -> Vec<TileCoordinate> {
-    let [min_lat, max_lat] = lats;
-    let [min_lon, max_lon] = lons;
+pub fn get_tile_coords(area: &Area, zoom: u32) -> Vec<TileCoordinate> {
+    let [min_lat, max_lat] = area.get_bounding_box().get_lats();
+    let [min_lon, max_lon] = area.get_bounding_box().get_lons();
 
     let top_left = deg2num(max_lat, min_lon, zoom);
     let bottom_right = deg2num(min_lat, max_lon, zoom);
@@ -42,7 +47,10 @@ pub fn get_tile_coords(lats: [f64; 2], lons: [f64; 2], zoom: u32) -> Vec<TileCoo
 
     for x in top_left.x..=bottom_right.x {
         for y in top_left.y..=bottom_right.y {
-            result.push(TileCoordinate::new(x, y, zoom));
+            let coord = TileCoordinate::new(x, y, zoom);
+            if area.intersects_tile(&coord) {
+                result.push(coord);
+            }
         }
     }
     result
@@ -0,0 +1,97 @@
+// Parses numeric OSM tag values into a fixed unit per tag:
+// weights in tonnes, lengths in metres, inclines in percent and speeds in km/h.
+pub fn parse_numeric_tag(key: &str, value: &str) -> Option<f64> {
+    let key = key.trim_start_matches("osm:");
+    match key {
+        "maxweight" | "maxweightrating" | "maxaxleload" | "maxbogieweight" | "weight" => {
+            parse_weight(value)
+        }
+        "width" | "maxwidth" | "est_width" | "height" | "maxheight" | "length" | "maxlength" => {
+            parse_length(value)
+        }
+        "incline" => parse_incline(value),
+        "maxspeed" | "minspeed" | "maxspeed:forward" | "maxspeed:backward" | "maxspeed:advisory" => {
+            parse_speed(value)
+        }
+        _ => parse_number(value),
+    }
+}
+
+// splits "7.5 t" into (7.5, "t")
+fn split_unit(value: &str) -> Option<(f64, &str)> {
+    let value = value.trim();
+    let end = value
+        .char_indices()
+        .find(|(i, c)| !(c.is_ascii_digit() || *c == '.' || *c == ',' || (*i == 0 && (*c == '-' || *c == '+'))))
+        .map(|(i, _)| i)
+        .unwrap_or_else(|| value.len());
+
+    let number = value[..end].replace(',', ".").parse::<f64>().ok()?;
+    Some((number, value[end..].trim()))
+}
+
+pub fn parse_number(value: &str) -> Option<f64> {
+    match split_unit(value)? {
+        (number, "") => Some(number),
+        _ => None,
+    }
+}
+
+pub fn parse_weight(value: &str) -> Option<f64> {
+    let (number, unit) = split_unit(value)?;
+    let factor = match unit {
+        "" | "t" => 1.,
+        "kg" => 0.001,
+        "lbs" | "lb" => 0.000_453_592_37,
+        "st" => 0.907_184_74, // short tons
+        "lt" => 1.016_046_908_8, // long tons
+        _ => return None,
+    };
+    Some(number * factor)
+}
+
+pub fn parse_length(value: &str) -> Option<f64> {
+    let value = value.trim();
+
+    // imperial notation, e.g. 8'6"
+    if let Some(feet_end) = value.find('\'') {
+        let feet = value[..feet_end].trim().parse::<f64>().ok()?;
+        let rest = value[feet_end + 1..].trim().trim_end_matches('"').trim();
+        let inches = if rest.is_empty() { 0. } else { rest.parse::<f64>().ok()? };
+        return Some(feet * 0.3048 + inches * 0.0254);
+    }
+
+    let (number, unit) = split_unit(value)?;
+    let factor = match unit {
+        "" | "m" => 1.,
+        "cm" => 0.01,
+        "mm" => 0.001,
+        "km" => 1000.,
+        "ft" => 0.3048,
+        "in" | "\"" => 0.0254,
+        "mi" => 1609.344,
+        "nmi" => 1852.,
+        _ => return None,
+    };
+    Some(number * factor)
+}
+
+pub fn parse_incline(value: &str) -> Option<f64> {
+    let (number, unit) = split_unit(value)?;
+    match unit {
+        "" | "%" => Some(number),
+        "°" | "deg" => Some(number.to_radians().tan() * 100.),
+        _ => None,
+    }
+}
+
+pub fn parse_speed(value: &str) -> Option<f64> {
+    let (number, unit) = split_unit(value)?;
+    let factor = match unit {
+        "" | "km/h" | "kmh" | "kph" => 1.,
+        "mph" => 1.609_344,
+        "knots" | "kn" => 1.852,
+        _ => return None,
+    };
+    Some(number * factor)
+}
@@ -0,0 +1,42 @@
+// LEB128 style variable length integers, small values take a single byte
+pub fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+pub fn read_varint(data: &[u8], position: &mut usize) -> Option<u64> {
+    let mut result: u64 = 0;
+    let mut shift = 0;
+    loop {
+        let byte = *data.get(*position)?;
+        *position += 1;
+        if shift > 63 {
+            return None;
+        }
+        result |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(result);
+        }
+        shift += 7;
+    }
+}
+
+// maps signed values to unsigned ones so that small deltas stay small
+pub fn zigzag_encode(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+pub fn zigzag_decode(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+pub fn write_signed_varint(out: &mut Vec<u8>, value: i64) {
+    write_varint(out, zigzag_encode(value));
+}
+
+pub fn read_signed_varint(data: &[u8], position: &mut usize) -> Option<i64> {
+    read_varint(data, position).map(zigzag_decode)
+}